@@ -0,0 +1,53 @@
+use sim::agility::{AgilitySimConfig, AgilitySimResult, Obstacle, format_agility_results, sim};
+use sim::profit::{DEFAULT_PRICE_FILE, PriceList, format_profit_results};
+use sim::batch::{Convergence, run_until_converged};
+
+use rust_decimal_macros::dec;
+use kdam::tqdm;
+use std::env;
+
+
+fn obstacle(name: &str, time: rust_decimal::Decimal, xp: i32, gp: i32, fail_chance: f32, fail_damage_max: i32) -> Obstacle {
+    Obstacle {
+        name: name.to_string(),
+        time,
+        xp,
+        gp,
+        fail_chance,
+        fail_damage_max,
+        ..Obstacle::default()
+    }
+}
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Optional course file, the course below is used without it
+    let config = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => AgilitySimConfig::load_from_file(path).expect("Failed to load course file"),
+        None => AgilitySimConfig::new(
+            dec!(8), // in seconds
+            8,
+            720,
+            vec![
+                obstacle("Rope Climb", dec!(4), 12, 30, 0.05, 20),
+                obstacle("Monkey Bars", dec!(5), 18, 45, 0.08, 30),
+                obstacle("Balance Beam", dec!(6), 25, 60, 0.1, 40),
+            ],
+        ),
+    };
+
+    // With --target-error the batch runs until the metric has converged instead of a fixed count
+    let convergence = Convergence::from_args(&args).unwrap_or_else(|e| panic!("{}", e));
+    let sims: Vec<AgilitySimResult> = match convergence {
+        Some(convergence) => {
+            let sims = run_until_converged(&convergence, || sim(&config));
+            println!("Ran {} simulations", sims.len());
+            sims
+        }
+        None => tqdm!(0..5000).map(|_| sim(&config)).collect(),
+    };
+
+    let prices = PriceList::load_from_file(DEFAULT_PRICE_FILE).unwrap_or_default();
+    println!("\n{}\n{}\n", format_agility_results(&sims), format_profit_results(&sims, &prices));
+}
@@ -0,0 +1,201 @@
+use std::env;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sim::batch::{Metric, Metrics};
+use sim::breakeven::{BreakEvenConfig, break_even, format_break_even_results};
+use sim::compare::{compare, format_comparison_results};
+use sim::sensitivity::{format_sensitivity_results, sensitivity};
+use sim::sweep::{SweepAxis, format_sweep_csv, format_sweep_table, numeric_fields, sweep};
+use sim::{fighting, thieving};
+
+const USAGE: &str = "\
+Usage: analysis-cli <command> <thieving|fighting> [args] [--iterations=N] [--replications=N] [--csv]
+Commands:
+  sweep <metric> <field=from:to:steps> [field=from:to:steps]
+  sensitivity <metric> [field...]  (all numeric fields by default)
+  compare [a.json] <b.json>  (a defaults to the built in config)
+  break-even <metric> <target> <field=low:high>  (e.g. survival 0.99 max_health=500:3000)
+    estimated from simulated batches, bisecting over whole values or 0.1 s steps for times
+Metrics: time, gold, kills, xp, survival";
+
+
+struct Options {
+    args: Vec<String>,
+    iterations: usize,
+    replications: usize,
+    csv: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Self {
+        let mut options = Self { args: Vec::new(), iterations: 5000, replications: 5, csv: false };
+        for arg in args {
+            if let Some(iterations) = arg.strip_prefix("--iterations=") {
+                options.iterations = iterations.parse().expect("Invalid iterations count");
+            } else if let Some(replications) = arg.strip_prefix("--replications=") {
+                options.replications = replications.parse().expect("Invalid replications count");
+            } else if arg == "--csv" {
+                options.csv = true;
+            } else {
+                options.args.push(arg.clone());
+            }
+        }
+        options
+    }
+
+    fn metric(&self, index: usize) -> Metric {
+        let metric = self.args.get(index).unwrap_or_else(|| panic!("{}", USAGE));
+        metric.parse().unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+
+fn sweep_command<C, R, F>(options: &Options, config: &C, sim: F)
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let metric = options.metric(0);
+    let axes = options.args[1..].iter()
+        .map(|a| a.parse())
+        .collect::<Result<Vec<SweepAxis>, String>>()
+        .unwrap_or_else(|e| panic!("{}", e));
+    let points = sweep(config, &axes, metric, options.iterations, |c| sim(c, &mut StdRng::from_entropy()))
+        .unwrap_or_else(|e| panic!("{}", e));
+    if options.csv {
+        print!("{}", format_sweep_csv(&axes, metric, &points));
+    } else {
+        println!("\n{}", format_sweep_table(&axes, metric, &points));
+    }
+}
+
+
+fn sensitivity_command<C, R, F>(options: &Options, config: &C, sim: F)
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let metric = options.metric(0);
+    let fields = if options.args.len() > 1 { options.args[1..].to_vec() } else { numeric_fields(config) };
+    let results = sensitivity(config, &fields, metric, options.iterations, sim).unwrap_or_else(|e| panic!("{}", e));
+    println!("\n{}", format_sensitivity_results(&results, metric));
+}
+
+
+// Reads a sim config, or the config inside a GUI save file
+fn load_config<C: DeserializeOwned>(path: &str) -> C {
+    let json = std::fs::read(path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+    let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap_or_else(|e| panic!("Invalid json in {}: {}", path, e));
+    if let Some(config) = value.get_mut("config") {
+        value = config.take();
+    }
+    serde_json::from_value(value).unwrap_or_else(|e| panic!("Invalid config in {}: {}", path, e))
+}
+
+
+fn compare_command<C, R, F>(options: &Options, config: &C, sim: F)
+where
+    C: Clone + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let (a, b) = match options.args.as_slice() {
+        [b] => (config.clone(), load_config(b)),
+        [a, b] => (load_config(a), load_config(b)),
+        _ => panic!("{}", USAGE),
+    };
+    let comparisons = compare(&a, &b, options.iterations, sim);
+    println!("\n{}", format_comparison_results(&comparisons));
+}
+
+
+fn break_even_command<C, R, F>(options: &Options, config: &C, sim: F)
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let metric = options.metric(0);
+    let (target, range) = match &options.args[1..] {
+        [target, range] => (target, range),
+        _ => panic!("{}", USAGE),
+    };
+    let (field, bounds) = range.split_once('=').unwrap_or_else(|| panic!("Invalid range: {}, expected field=low:high", range));
+    let (low, high) = bounds.split_once(':').unwrap_or_else(|| panic!("Invalid range: {}, expected field=low:high", range));
+    let search = BreakEvenConfig {
+        field: field.to_string(),
+        low: low.parse().expect("Invalid low bound"),
+        high: high.parse().expect("Invalid high bound"),
+        metric,
+        target: target.parse().expect("Invalid target"),
+        iterations: options.iterations,
+        replications: options.replications,
+    };
+    let result = break_even(config, &search, sim).unwrap_or_else(|e| panic!("{}", e));
+    println!("\n{}", format_break_even_results(&result));
+}
+
+
+fn run<C, R, F>(command: &str, options: &Options, config: C, sim: F)
+where
+    C: Clone + Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    match command {
+        "sweep" => sweep_command(options, &config, sim),
+        "sensitivity" => sensitivity_command(options, &config, sim),
+        "compare" => compare_command(options, &config, sim),
+        "break-even" => break_even_command(options, &config, sim),
+        _ => panic!("Unknown command: {}\n{}", command, USAGE),
+    }
+}
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = Options::parse(&args[2..]);
+    match args[1].as_str() {
+        "thieving" => {
+            let config = thieving::ThievingSimConfig::new(
+                dec!(8), // in seconds
+                8,
+                720,
+                dec!(2.6), // in seconds
+                0.9,
+                0,
+                157,
+                51,
+                1212,
+            );
+            run(&args[0], &options, config, thieving::sim_with_rng)
+        }
+        "fighting" => {
+            let config = fighting::FightingSimConfig::new(
+                720,
+                8,
+                8,
+                1,
+                111,
+                0.76,
+                3.0,
+                300,
+                0,
+                116,
+                0.35,
+                2.4,
+            );
+            run(&args[0], &options, config, fighting::sim_with_rng)
+        }
+        skill => panic!("Unknown skill: {}\n{}", skill, USAGE),
+    }
+}
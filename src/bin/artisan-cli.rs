@@ -0,0 +1,45 @@
+use sim::artisan::{ArtisanSimConfig, ArtisanSimResult, Recipe, format_artisan_results, sim};
+use sim::profit::{DEFAULT_PRICE_FILE, Items, PriceList, format_profit_results};
+use sim::batch::{Convergence, run_until_converged};
+
+use rust_decimal_macros::dec;
+use kdam::tqdm;
+use std::env;
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Optional recipes and bank file, cooking the bank below is used without it
+    let config = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => ArtisanSimConfig::load_from_file(path).expect("Failed to load artisan config file"),
+        None => {
+            let recipe = Recipe {
+                output: "Lobster".to_string(),
+                output_amount: 1,
+                inputs: Items::from([("Raw Lobster".to_string(), 1)]),
+                interval: dec!(3),
+                xp: 120,
+                fail_chance: 0.15,
+                failed_output: Some("Burnt Lobster".to_string()),
+            };
+            let mut config = ArtisanSimConfig::new(vec![recipe], Items::from([("Raw Lobster".to_string(), 2000)]));
+            config.preservation_chance = 0.05;
+            config.double_chance = 0.05;
+            config
+        }
+    };
+
+    // With --target-error the batch runs until the metric has converged instead of a fixed count
+    let convergence = Convergence::from_args(&args).unwrap_or_else(|e| panic!("{}", e));
+    let sims: Vec<ArtisanSimResult> = match convergence {
+        Some(convergence) => {
+            let sims = run_until_converged(&convergence, || sim(&config));
+            println!("Ran {} simulations", sims.len());
+            sims
+        }
+        None => tqdm!(0..5000).map(|_| sim(&config)).collect(),
+    };
+
+    let prices = PriceList::load_from_file(DEFAULT_PRICE_FILE).unwrap_or_default();
+    println!("\n{}\n{}\n", format_artisan_results(&sims), format_profit_results(&sims, &prices));
+}
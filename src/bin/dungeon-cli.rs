@@ -0,0 +1,66 @@
+use std::env;
+use kdam::tqdm;
+use sim::batch::{Convergence, run_until_converged};
+use sim::dungeon::{DungeonSimResult, MonsterSequence, format_dungeon_results, sim};
+use sim::fighting::{DamageOverTime, DotKind, FightingSimConfig, Monster, SpecialAttack};
+use sim::profit::{DEFAULT_PRICE_FILE, PriceList, format_profit_results};
+use rust_decimal_macros::dec;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config = FightingSimConfig::new(
+        720,
+        8,
+        8,
+        1,
+        111,
+        0.76,
+        3.0,
+        300,
+        0,
+        116,
+        0.35,
+        2.4,
+    );
+    let dungeon = MonsterSequence::Dungeon(vec![
+        Monster::new(200, 0, 60, 0.3, 3.0),
+        Monster::new(250, 0, 80, 0.35, 2.6),
+        Monster::new(300, 0, 116, 0.35, 2.4),
+        Monster {
+            specials: vec![
+                SpecialAttack {
+                    chance: 0.15,
+                    hits: 3,
+                    damage_min: 10,
+                    damage_max: 50,
+                    stun: dec!(3),
+                    ..SpecialAttack::default()
+                },
+                SpecialAttack {
+                    chance: 0.1,
+                    hits: 1,
+                    damage_min: 0,
+                    damage_max: 80,
+                    dot: Some(DamageOverTime { kind: DotKind::Burn, damage: 10, ticks: 5, interval: dec!(2) }),
+                    heal: 50,
+                    ..SpecialAttack::default()
+                },
+            ],
+            ..Monster::new(600, 0, 150, 0.4, 3.0)
+        },
+    ]);
+    // With --target-error the batch runs until the metric has converged instead of a fixed count
+    let convergence = Convergence::from_args(&args).unwrap_or_else(|e| panic!("{}", e));
+    let run = || sim(&config, &dungeon).unwrap_or_else(|e| panic!("{}", e));
+    let sims: Vec<DungeonSimResult> = match convergence {
+        Some(convergence) => {
+            let sims = run_until_converged(&convergence, run);
+            println!("Ran {} simulations", sims.len());
+            sims
+        }
+        None => tqdm!(0..5000).map(|_| run()).collect(),
+    };
+
+    let prices = PriceList::load_from_file(DEFAULT_PRICE_FILE).unwrap_or_default();
+    println!("\n{}\n{}\n", format_dungeon_results(&sims), format_profit_results(&sims, &prices));
+}
@@ -0,0 +1,46 @@
+use sim::gathering::{GatheringSimConfig, GatheringSimResult, Mastery, Node, format_gathering_results, sim};
+use sim::profit::{DEFAULT_PRICE_FILE, ItemDrop, PriceList, format_profit_results};
+use sim::batch::{Convergence, run_until_converged};
+
+use rust_decimal_macros::dec;
+use kdam::tqdm;
+use std::env;
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Optional skill config file, the mining config below is used without it
+    let config = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => GatheringSimConfig::load_from_file(path).expect("Failed to load gathering config file"),
+        None => {
+            let mut config = GatheringSimConfig::new("Iron Ore", dec!(3), 14);
+            config.double_chance = 0.05;
+            config.rare_drops = vec![
+                ItemDrop { item: "Topaz".to_string(), chance: 0.01, min: 1, max: 1 },
+                ItemDrop { item: "Sapphire".to_string(), chance: 0.005, min: 1, max: 1 },
+            ];
+            config.node = Some(Node { hp: 6, respawn_time: dec!(5) });
+            config.mastery = Some(Mastery {
+                xp: 0.0,
+                xp_per_action: 30.0,
+                double_chance_per_level: 0.002,
+                node_hp_per_level: 0.1,
+            });
+            config
+        }
+    };
+
+    // With --target-error the batch runs until the metric has converged instead of a fixed count
+    let convergence = Convergence::from_args(&args).unwrap_or_else(|e| panic!("{}", e));
+    let sims: Vec<GatheringSimResult> = match convergence {
+        Some(convergence) => {
+            let sims = run_until_converged(&convergence, || sim(&config));
+            println!("Ran {} simulations", sims.len());
+            sims
+        }
+        None => tqdm!(0..5000).map(|_| sim(&config)).collect(),
+    };
+
+    let prices = PriceList::load_from_file(DEFAULT_PRICE_FILE).unwrap_or_default();
+    println!("\n{}\n{}\n", format_gathering_results(&sims), format_profit_results(&sims, &prices));
+}
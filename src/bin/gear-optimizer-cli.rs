@@ -0,0 +1,57 @@
+use std::env;
+use rust_decimal_macros::dec;
+use sim::batch::Metric;
+use sim::equipment::{DEFAULT_GEAR_FILE, Equipment, Skill};
+use sim::optimizer::{OptimizerConfig, format_optimizer_results, optimize};
+use sim::{fighting, thieving};
+
+// Usage: gear-optimizer-cli <thieving|fighting> <time|gold|kills|xp|survival> [top]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let skill: Skill = args.get(1).map(String::as_str).unwrap_or("thieving").parse().unwrap_or_else(|e| panic!("{}", e));
+    let objective: Metric = args.get(2).map(String::as_str).unwrap_or("gold").parse().unwrap();
+    let top = args.get(3).map(|t| t.parse().unwrap()).unwrap_or(5);
+
+    let equipment = Equipment::load_from_file(DEFAULT_GEAR_FILE).expect("Failed to load gear file");
+    let config = OptimizerConfig::new(skill, objective, top, 1000);
+
+    let results = match skill {
+        Skill::Thieving => {
+            let base = thieving::ThievingSimConfig::new(
+                dec!(8), // in seconds
+                8,
+                720,
+                dec!(2.6), // in seconds
+                0.9,
+                0,
+                157,
+                51,
+                1212,
+            );
+            optimize(&equipment.inventory, &equipment.sets, &config, |stats| {
+                thieving::sim(&stats.apply_thieving(&base))
+            })
+        }
+        Skill::Fighting => {
+            let base = fighting::FightingSimConfig::new(
+                720,
+                8,
+                8,
+                1,
+                111,
+                0.76,
+                3.0,
+                300,
+                0,
+                116,
+                0.35,
+                2.4,
+            );
+            optimize(&equipment.inventory, &equipment.sets, &config, |stats| {
+                fighting::sim(&stats.apply_fighting(&base))
+            })
+        }
+    };
+
+    println!("\n{}", format_optimizer_results(&results, objective));
+}
@@ -0,0 +1,110 @@
+use std::env;
+use rust_decimal_macros::dec;
+use sim::{fighting, thieving};
+use sim::food::Food;
+use sim::potion::{Potion, PotionEffect};
+use sim::offline::{DEFAULT_CAP_HOURS, OfflineConfig, format_offline_results, offline_report};
+
+const USAGE: &str = "\
+Usage: offline-cli <thieving|fighting> [cap hours] [--bonus-hours=N] [--health=N] [--food=N] [--potions=N] [--iterations=N]
+Simulates an offline session and reports what you would find on return at every hour.
+--food and --potions bring lobsters and a potion of the skill, with that many in stock.";
+
+
+// Brought along when --food is given, the built in configs have none
+fn lobster() -> Food {
+    Food {
+        name: "Lobster".to_string(),
+        heal: 120,
+        stock: 0,
+        eat_below: 0.5,
+    }
+}
+
+fn potion(name: &str, effect: PotionEffect) -> Potion {
+    Potion {
+        name: name.to_string(),
+        effect,
+        charges: 20,
+        stock: 0,
+    }
+}
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let skill = match positional.first() {
+        Some(skill) => skill.as_str(),
+        None => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+    let value = |name: &str| args.iter()
+        .find_map(|a| a.strip_prefix(&format!("--{}=", name)))
+        .map(|v| v.parse::<u32>().unwrap_or_else(|_| panic!("Invalid {}: {}", name, v)));
+
+    let mut offline = OfflineConfig::new(positional.get(1).map_or(DEFAULT_CAP_HOURS, |h| h.parse().expect("Invalid cap hours")));
+    offline.bonus_hours = value("bonus-hours").unwrap_or(0);
+    offline.start_health = value("health").map(|h| h as i32);
+    offline.food_stock = value("food");
+    offline.potion_stock = value("potions");
+    let iterations = value("iterations").unwrap_or(1000) as usize;
+
+    let marks = match skill {
+        "thieving" => {
+            let mut config = thieving::ThievingSimConfig::new(
+                dec!(8), // in seconds
+                8,
+                720,
+                dec!(2.6), // in seconds
+                0.9,
+                0,
+                157,
+                51,
+                1212,
+            );
+            if offline.food_stock.is_some() {
+                config.food = Some(lobster());
+            }
+            if offline.potion_stock.is_some() {
+                config.potion = Some(potion("Thieving Potion", PotionEffect::SuccessChance(0.1)));
+            }
+            let config = offline.apply_thieving(&config);
+            let start_health = config.start_health.unwrap_or(config.max_health);
+            offline_report(iterations, offline.hours(), start_health, || {
+                thieving::sim_traced(&config, &mut rand::thread_rng()).1
+            })
+        }
+        "fighting" => {
+            let mut config = fighting::FightingSimConfig::new(
+                720,
+                8,
+                8,
+                1,
+                111,
+                0.76,
+                3.0,
+                300,
+                0,
+                116,
+                0.35,
+                2.4,
+            );
+            if offline.food_stock.is_some() {
+                config.food = Some(lobster());
+            }
+            if offline.potion_stock.is_some() {
+                config.potion = Some(potion("Damage Potion", PotionEffect::Damage(0.1)));
+            }
+            let config = offline.apply_fighting(&config);
+            let start_health = config.start_health.unwrap_or(config.player_health) as i32;
+            offline_report(iterations, offline.hours(), start_health, || {
+                fighting::sim_traced(&config, &mut rand::thread_rng()).1
+            })
+        }
+        skill => panic!("Unknown skill: {}\n{}", skill, USAGE),
+    };
+    println!("\n{}", format_offline_results(&marks));
+}
@@ -0,0 +1,59 @@
+use sim::artisan::{self, ArtisanSimConfig, Recipe};
+use sim::fighting::{self, FightingSimConfig};
+use sim::food::Food;
+use sim::gathering::{self, GatheringSimConfig};
+use sim::pipeline::{Stage, format_pipeline_results, solve};
+use sim::profit::Items;
+use sim::batch::run_batch;
+
+use rust_decimal_macros::dec;
+use std::env;
+
+const USAGE: &str = "\
+Usage: pipeline-cli [combat hours] [--iterations=N]
+Works out how many hours of fishing and cooking keep the fight supplied with food.";
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let hours: f64 = args.iter().find(|a| !a.starts_with("--")).map_or(1.0, |h| h.parse().expect("Invalid combat hours"));
+    let iterations: usize = args.iter()
+        .find_map(|a| a.strip_prefix("--iterations="))
+        .map_or(1000, |n| n.parse().expect("Invalid iterations"));
+
+    let mut fishing = GatheringSimConfig::new("Raw Lobster", dec!(5), 90);
+    fishing.double_chance = 0.05;
+    fishing.max_time = Some(dec!(3600));
+
+    let lobster = Recipe {
+        output: "Lobster".to_string(),
+        output_amount: 1,
+        inputs: Items::from([("Raw Lobster".to_string(), 1)]),
+        interval: dec!(3),
+        xp: 120,
+        fail_chance: 0.15,
+        failed_output: Some("Burnt Lobster".to_string()),
+    };
+    let cooking = ArtisanSimConfig::new(vec![lobster], Items::from([("Raw Lobster".to_string(), 1000)]));
+
+    let mut fight = FightingSimConfig::new(720, 8, 8, 1, 111, 0.76, 3.0, 300, 0, 116, 0.35, 2.4);
+    fight.food = Some(Food {
+        name: "Lobster".to_string(),
+        heal: 120,
+        stock: u32::MAX,
+        eat_below: 0.5,
+    });
+
+    // Producers first, every stage supplies the ones after it
+    let stages = [
+        Stage::from_results("fishing", &run_batch(iterations, || gathering::sim(&fishing))),
+        Stage::from_results("cooking", &run_batch(iterations, || artisan::sim(&cooking))),
+    ];
+    let target = Stage::from_results("fighting", &run_batch(iterations, || fighting::sim(&fight)));
+    let pipeline = solve(&stages, &target, hours).unwrap_or_else(|e| panic!("{}", e));
+    println!("{}", format_pipeline_results(&pipeline));
+}
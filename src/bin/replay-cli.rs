@@ -0,0 +1,82 @@
+use std::env;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal_macros::dec;
+use sim::{fighting, thieving};
+use sim::batch::{Metric, Metrics};
+use sim::trace::{RunPick, Trace, format_trace, pick_seed};
+
+const USAGE: &str = "\
+Usage: replay-cli <thieving|fighting> [seed] [--pick=worst|median|best] [--metric=time] [--ndjson] [--out=trace.ndjson]
+Replays a single seeded run and prints every event. A random seed is used when none is given.
+With --pick the seed is taken from a batch of 1000 runs ranked by the metric.";
+const PICK_ITERATIONS: usize = 1000;
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let skill = match positional.first() {
+        Some(skill) => skill.as_str(),
+        None => {
+            println!("{}", USAGE);
+            return;
+        }
+    };
+    let value = |name: &str| args.iter().find_map(|a| a.strip_prefix(&format!("--{}=", name)));
+    let pick: Option<RunPick> = value("pick").map(|p| p.parse().unwrap_or_else(|e| panic!("{}", e)));
+    let metric: Metric = value("metric").unwrap_or("time").parse().unwrap_or_else(|e| panic!("{}", e));
+    let choose_seed = |sim: &dyn Fn(&mut StdRng) -> f64| match (positional.get(1), pick) {
+        (Some(seed), _) => seed.parse().expect("Invalid seed"),
+        (None, Some(pick)) => pick_seed(PICK_ITERATIONS, pick, sim),
+        (None, None) => rand::random::<u32>() as u64,
+    };
+
+    let (seed, summary, trace): (u64, String, Trace) = match skill {
+        "thieving" => {
+            let config = thieving::ThievingSimConfig::new(
+                dec!(8), // in seconds
+                8,
+                720,
+                dec!(2.6), // in seconds
+                0.9,
+                0,
+                157,
+                51,
+                1212,
+            );
+            let seed = choose_seed(&|rng| thieving::sim_with_rng(&config, rng).metric(metric));
+            let (result, trace) = thieving::sim_traced(&config, &mut StdRng::seed_from_u64(seed));
+            (seed, thieving::format_thieve_results(&[result]), trace)
+        }
+        "fighting" => {
+            let config = fighting::FightingSimConfig::new(
+                720,
+                8,
+                8,
+                1,
+                111,
+                0.76,
+                3.0,
+                300,
+                0,
+                116,
+                0.35,
+                2.4,
+            );
+            let seed = choose_seed(&|rng| fighting::sim_with_rng(&config, rng).metric(metric));
+            let (result, trace) = fighting::sim_traced(&config, &mut StdRng::seed_from_u64(seed));
+            (seed, fighting::format_fighting_results(&[result]), trace)
+        }
+        skill => panic!("Unknown skill: {}\n{}", skill, USAGE),
+    };
+
+    if let Some(path) = args.iter().find_map(|a| a.strip_prefix("--out=")) {
+        trace.save_to_file(path).expect("Failed to save trace");
+    }
+    if args.iter().any(|a| a == "--ndjson") {
+        print!("{}", trace.to_ndjson());
+    } else {
+        println!("Seed: {}\n\n{}\n{}", seed, format_trace(&trace), summary);
+    }
+}
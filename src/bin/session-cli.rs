@@ -0,0 +1,67 @@
+use std::env;
+use rust_decimal_macros::dec;
+use sim::{fighting, thieving};
+use sim::offline::DEFAULT_CAP_HOURS;
+use sim::session::{Session, chain, format_session_results};
+
+const USAGE: &str = "\
+Usage: session-cli <thieving|fighting> <online:hours|offline:hours>... [--cap=hours] [--xp=N] [--iterations=N]
+Simulates sessions back to back, health, food and potions carry over from one to the next.
+Xp and the level are totalled across the sessions, the level does not change the config.
+Example: session-cli thieving online:2 offline:8 online:1";
+
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    if positional.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+    let sessions = positional[1..].iter()
+        .map(|s| s.parse())
+        .collect::<Result<Vec<Session>, String>>()
+        .unwrap_or_else(|e| panic!("{}", e));
+    let value = |name: &str| args.iter().find_map(|a| a.strip_prefix(&format!("--{}=", name)));
+    let cap = value("cap").map_or(DEFAULT_CAP_HOURS, |c| c.parse().expect("Invalid cap"));
+    let start_xp: f64 = value("xp").map_or(0.0, |xp| xp.parse().expect("Invalid xp"));
+    let iterations = value("iterations").map_or(1000, |i| i.parse().expect("Invalid iterations count"));
+
+    let output = match positional[0].as_str() {
+        "thieving" => {
+            let config = thieving::ThievingSimConfig::new(
+                dec!(8), // in seconds
+                8,
+                720,
+                dec!(2.6), // in seconds
+                0.9,
+                0,
+                157,
+                51,
+                1212,
+            );
+            let results = chain(&sessions, cap, iterations, |start| thieving::sim(&start.apply_thieving(&config)));
+            format_session_results(&sessions, &results, start_xp, cap)
+        }
+        "fighting" => {
+            let config = fighting::FightingSimConfig::new(
+                720,
+                8,
+                8,
+                1,
+                111,
+                0.76,
+                3.0,
+                300,
+                0,
+                116,
+                0.35,
+                2.4,
+            );
+            let results = chain(&sessions, cap, iterations, |start| fighting::sim(&start.apply_fighting(&config)));
+            format_session_results(&sessions, &results, start_xp, cap)
+        }
+        skill => panic!("Unknown skill: {}\n{}", skill, USAGE),
+    };
+    println!("\n{}", output);
+}
@@ -0,0 +1,66 @@
+use std::env;
+use kdam::tqdm;
+use sim::batch::{Convergence, run_until_converged};
+use sim::fighting::{FightingSimConfig, Monster};
+use sim::slayer::{SlayerSimResult, SlayerTask, SlayerTier, format_slayer_results, sim};
+use sim::profit::{DEFAULT_PRICE_FILE, PriceList, format_profit_results};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config = FightingSimConfig::new(
+        720,
+        8,
+        8,
+        1,
+        111,
+        0.76,
+        3.0,
+        300,
+        0,
+        116,
+        0.35,
+        2.4,
+    );
+    let tier = SlayerTier {
+        tasks: vec![
+            SlayerTask {
+                monster: Monster::new(150, 0, 30, 0.3, 3.0),
+                weight: 3,
+                min_kills: 10,
+                max_kills: 30,
+                coins_per_kill: 15,
+                xp_per_kill: 150,
+            },
+            SlayerTask {
+                monster: Monster::new(200, 0, 45, 0.3, 2.8),
+                weight: 2,
+                min_kills: 10,
+                max_kills: 25,
+                coins_per_kill: 20,
+                xp_per_kill: 200,
+            },
+            SlayerTask {
+                monster: Monster::new(300, 0, 60, 0.35, 2.4),
+                weight: 1,
+                min_kills: 5,
+                max_kills: 15,
+                coins_per_kill: 30,
+                xp_per_kill: 300,
+            },
+        ],
+    };
+    // With --target-error the batch runs until the metric has converged instead of a fixed count
+    let convergence = Convergence::from_args(&args).unwrap_or_else(|e| panic!("{}", e));
+    let run = || sim(&config, &tier).unwrap_or_else(|e| panic!("{}", e));
+    let sims: Vec<SlayerSimResult> = match convergence {
+        Some(convergence) => {
+            let sims = run_until_converged(&convergence, run);
+            println!("Ran {} simulations", sims.len());
+            sims
+        }
+        None => tqdm!(0..5000).map(|_| run()).collect(),
+    };
+
+    let prices = PriceList::load_from_file(DEFAULT_PRICE_FILE).unwrap_or_default();
+    println!("\n{}\n{}\n", format_slayer_results(&sims), format_profit_results(&sims, &prices));
+}
@@ -1,7 +1,14 @@
-use sim::thieving::{ThievingSimConfig, ThievingSimResult, format_thieve_results, sim};
+use sim::thieving::{MAX_TIME, ThievingSimConfig, ThievingSimResult, format_thieve_results, sim, sim_traced};
+use sim::profit::{DEFAULT_PRICE_FILE, PriceList, format_profit_results};
+use sim::equipment::{DEFAULT_GEAR_FILE, Equipment};
+use sim::batch::{Convergence, run_until_converged};
+use sim::envelope::{DEFAULT_INTERVAL, HealthEnvelope, format_envelope_csv};
+use sim::summoning::{Summoning, format_summoning_results};
 
 use rust_decimal_macros::dec;
+use rust_decimal::prelude::ToPrimitive;
 use kdam::tqdm;
+use std::env;
 
 
 fn main() {
@@ -17,11 +24,50 @@ fn main() {
         51,
         1212,
     );
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Optional loadout name, its gear is added on top of the config above
+    let config = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(name) => {
+            let equipment = Equipment::load_from_file(DEFAULT_GEAR_FILE).expect("Failed to load gear file");
+            let stats = equipment.stats(name).unwrap_or_else(|| panic!("Unknown loadout: {}", name));
+            stats.apply_thieving(&config)
+        }
+        None => config,
+    };
+    // With --summoning=<file> the familiars in the file are summoned
+    let mut config = config;
+    if let Some(path) = args.iter().find_map(|a| a.strip_prefix("--summoning=")) {
+        config.summoning = Summoning::load_from_file(path).expect("Failed to load summoning file");
+    }
 
-    let mut sims: Vec<ThievingSimResult> = Vec::new();
-    for _ in tqdm!(0..5000) {
-        sims.push(sim(&config));
+    // With --target-error the batch runs until the metric has converged instead of a fixed count
+    let convergence = Convergence::from_args(&args).unwrap_or_else(|e| panic!("{}", e));
+    // With --envelope=<file> runs are traced and health at checkpoints is saved as CSV
+    let envelope_path = args.iter().find_map(|a| a.strip_prefix("--envelope="));
+    let mut envelope = HealthEnvelope::new(config.max_health, MAX_TIME.to_f64().unwrap(), DEFAULT_INTERVAL);
+    let mut run = || match envelope_path {
+        Some(_) => {
+            let (result, trace) = sim_traced(&config, &mut rand::thread_rng());
+            envelope.add(&trace);
+            result
+        }
+        None => sim(&config),
+    };
+    let sims: Vec<ThievingSimResult> = match convergence {
+        Some(convergence) => {
+            let sims = run_until_converged(&convergence, &mut run);
+            println!("Ran {} simulations", sims.len());
+            sims
+        }
+        None => tqdm!(0..5000).map(|_| run()).collect(),
+    };
+    if let Some(path) = envelope_path {
+        std::fs::write(path, format_envelope_csv(&envelope.checkpoints())).expect("Failed to save envelope");
     }
 
-    println!("\n{}\n", format_thieve_results(&sims));
+    let prices = PriceList::load_from_file(DEFAULT_PRICE_FILE).unwrap_or_default();
+    println!("\n{}\n{}\n", format_thieve_results(&sims), format_profit_results(&sims, &prices));
+    if !config.summoning.is_empty() {
+        println!("{}", format_summoning_results(&sims));
+    }
 }
\ No newline at end of file
@@ -1,9 +1,21 @@
+mod chart;
+
 use std::env;
 use std::path::PathBuf;
-use iced::widget::{column, row, text, text_input, Column, text_editor, Space, progress_bar, button};
+use iced::widget::{column, row, text, text_input, Column, text_editor, Space, progress_bar, button, pick_list, Canvas, checkbox};
 use iced::settings::Settings;
 use sim::thieving;
 use sim::thieving::{ThievingSimConfig, ThievingSimResult};
+use sim::profit::{DEFAULT_PRICE_FILE, PriceList, format_profit_results};
+use sim::batch::{Convergence, Estimate, Metric, Metrics};
+use sim::compare::{Comparison, compare, format_comparison_results};
+use sim::sweep::{SweepAxis, SweepPoint, format_sweep_table, sweep};
+use sim::trace::{RunPick, Trace, format_trace, pick_seed};
+use sim::envelope::{DEFAULT_INTERVAL, HealthEnvelope};
+use rust_decimal::prelude::ToPrimitive;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use chart::Chart;
 use iced::{Size, Length, Theme};
 use std::sync::{Arc, RwLock};
 use native_dialog::FileDialog;
@@ -19,7 +31,7 @@ pub fn main() -> iced::Result {
     if !std::path::Path::new(DEFAULT_SAVE_DIR).exists() {
         std::fs::create_dir(DEFAULT_SAVE_DIR).unwrap();
     }
-    let size = Size::new(600.0, 500.0);
+    let size = Size::new(700.0, 800.0);
     let app = iced::program("Thieving simulation", ThievingGuiState::update, ThievingGuiState::view)
     .settings(Settings {
         window: iced::window::Settings {
@@ -38,7 +50,7 @@ pub fn main() -> iced::Result {
 #[derive(Debug, Clone)]
 enum Message {
     StartSim,
-    SimComplete(Option<ThievingSimResult>),
+    SimComplete(Option<(ThievingSimResult, Option<Trace>)>),
     StopSim,
     HealthRegenerationInterval(String),
     HealthRegenerationAmount(String),
@@ -50,10 +62,23 @@ enum Message {
     MinGold(String),
     MaxGold(String),
     SimsCount(String),
+    TargetError(String),
     SaveConfig(Option<PathBuf>),
     LoadConfig(Option<PathBuf>),
     OpenSaveConfigDialog,
     OpenLoadConfigDialog,
+    SweepAxis1(String),
+    SweepAxis2(String),
+    MetricSelected(Metric),
+    StartSweep,
+    SweepComplete(Result<(Vec<SweepAxis>, Vec<SweepPoint>), String>),
+    OpenCompareDialog,
+    CompareWith(Option<PathBuf>),
+    CompareComplete(Vec<Comparison>),
+    RunPickSelected(RunPick),
+    ShowEnvelope(bool),
+    ShowTimeline,
+    TimelineComplete(u64, Trace),
 }
 
 
@@ -62,6 +87,9 @@ struct ThievingConfigState {
     sims_count: u16,
     steal_success_chance: i32,
     config: ThievingSimConfig,
+    // Stops the run early once the standard error of the selected metric is below it, e.g. 500 or 1%
+    #[serde(default)]
+    target_error: String,
 }
 
 impl Default for ThievingConfigState {
@@ -85,6 +113,7 @@ impl Default for ThievingConfigState {
                         51,
                         1212,
                     ),
+                    target_error: String::new(),
                 }
             }
         }
@@ -104,6 +133,15 @@ impl ThievingConfigState {
         Ok(config)
     }
 
+    // Sims count is the iteration cap when a target error is set
+    fn convergence(&self, metric: Metric) -> Option<Convergence> {
+        let tolerance = self.target_error.parse().ok()?;
+        Some(Convergence {
+            max_iterations: self.sims_count as usize,
+            ..Convergence::new(metric, tolerance)
+        })
+    }
+
     fn clean_message(&self, message: String, allow_dots: bool) -> String {
         let filter = if allow_dots {
             |c: &char| c.is_digit(10) || *c == '.'
@@ -148,6 +186,9 @@ impl ThievingConfigState {
             Message::SimsCount(sims_count) => {
                 self.sims_count = self.clean_message(sims_count, false).parse().unwrap_or_default();
             }
+            Message::TargetError(target_error) => {
+                self.target_error = target_error.chars().filter(|c| c.is_ascii_digit() || *c == '.' || *c == '%').collect();
+            }
             _ => {}
         }
     }
@@ -168,6 +209,7 @@ impl ThievingConfigState {
             ("Min gold: ", &self.config.min_gold.to_string(), Message::MinGold as MessageConstructor),
             ("Max gold: ", &self.config.max_gold.to_string(), Message::MaxGold as MessageConstructor),
             ("Sims count: ", &self.sims_count.to_string(), Message::SimsCount as MessageConstructor),
+            ("Target error: ", &self.target_error, Message::TargetError as MessageConstructor),
         ].iter().cloned() {
             column = column.push(
                 row![
@@ -180,13 +222,32 @@ impl ThievingConfigState {
 }
 
 
+fn new_envelope(config: &ThievingSimConfig) -> HealthEnvelope {
+    HealthEnvelope::new(config.max_health, thieving::MAX_TIME.to_f64().unwrap(), DEFAULT_INTERVAL)
+}
+
+
+fn format_results(sims: &[ThievingSimResult], prices: &PriceList) -> String {
+    format!("{}\n{}", thieving::format_thieve_results(sims), format_profit_results(sims, prices))
+}
+
+
 struct ThievingGuiState {
     sim_result: text_editor::Content,
     sims: Vec<ThievingSimResult>,
+    prices: PriceList,
     progress: f32,
     config_stat: ThievingConfigState,
     is_started: Arc<RwLock<bool>>,
     theme: Theme,
+    sweep_axes: [String; 2],
+    metric: Metric,
+    is_sweeping: bool,
+    run_pick: RunPick,
+    // Runs are only traced when the health band is shown
+    show_envelope: bool,
+    envelope: HealthEnvelope,
+    chart: Chart,
 }
 
 
@@ -200,15 +261,24 @@ impl Default for ThievingGuiState {
     fn default() -> Self {
         let sims: Vec<ThievingSimResult> = Vec::new();
         let config_stat = ThievingConfigState::default();
+        let prices = PriceList::load_from_file(DEFAULT_PRICE_FILE).unwrap_or_default();
         Self {
             sim_result: text_editor::Content::with_text(
-                &thieving::format_thieve_results(&sims)
+                &format_results(&sims, &prices)
             ),
             sims,
+            prices,
             progress: 0.0,
-            config_stat,
             is_started: Arc::new(RwLock::new(false)),
             theme: Theme::default(),
+            sweep_axes: [String::from("steal_success_chance=0.8:0.99:20"), String::new()],
+            metric: Metric::GoldPerHour,
+            is_sweeping: false,
+            run_pick: RunPick::Worst,
+            show_envelope: false,
+            envelope: new_envelope(&config_stat.config),
+            chart: Chart::default(),
+            config_stat,
         }
     }
 }
@@ -222,14 +292,17 @@ impl ThievingGuiState {
         match &message {
             Message::StartSim => {
                 self.sims.clear();
+                self.envelope = new_envelope(&self.config_stat.config);
                 self.sim_result = text_editor::Content::with_text(
-                    &thieving::format_thieve_results(&self.sims)
+                    &format_results(&self.sims, &self.prices)
                 );
                 let mut is_started = self.is_started.write().unwrap();
                 *is_started = true;
-                let config = self.config_stat.config;
+                let config = self.config_stat.config.clone();
+                let traced = self.show_envelope;
                 iced::Command::batch((0..self.config_stat.sims_count).map(|id| {
                     let is_started_clone = self.is_started.clone();
+                    let config = config.clone();
                     iced::Command::perform(
                         async move {
                             let is_started= match is_started_clone.read() {
@@ -238,7 +311,12 @@ impl ThievingGuiState {
                             };
                             if is_started == true {
                                 println!("Start sim: {}", id);
-                                Some(thieving::sim(&config))
+                                if traced {
+                                    let (result, trace) = thieving::sim_traced(&config, &mut rand::thread_rng());
+                                    Some((result, Some(trace)))
+                                } else {
+                                    Some((thieving::sim(&config), None))
+                                }
                             } else {
                                 println!("Simulation stopped");
                                 None
@@ -255,21 +333,30 @@ impl ThievingGuiState {
                 iced::Command::none()
             }
             Message::SimComplete(sim) => {
-                if let Some(r) = sim {
-                    self.sims.push(*r);
+                if let Some((r, trace)) = sim {
+                    self.sims.push(r.clone());
+                    if let Some(trace) = trace {
+                        self.envelope.add(trace);
+                    }
                 }
                 if *self.is_started.read().unwrap() == true {
                     self.progress = self.sims.len() as f32;
                 }
                 if self.sims.len() as u16 % (self.config_stat.sims_count / 10) == 0 {
                     self.sim_result = text_editor::Content::with_text(
-                        &thieving::format_thieve_results(&self.sims)
+                        &format_results(&self.sims, &self.prices)
                     );
                 }
-                if self.sims.len() == self.config_stat.sims_count as usize {
+                let converged = self.sims.len() % Convergence::CHECK_INTERVAL == 0
+                    && self.config_stat.convergence(self.metric)
+                        .is_some_and(|c| c.is_converged(&Estimate::from_results(&self.sims, c.metric)));
+                if self.sims.len() == self.config_stat.sims_count as usize || converged {
                     self.sim_result = text_editor::Content::with_text(
-                        &thieving::format_thieve_results(&self.sims)
+                        &format_results(&self.sims, &self.prices)
                     );
+                    if self.show_envelope {
+                        self.chart = Chart::Band(self.envelope.checkpoints());
+                    }
                     let mut is_started = self.is_started.write().unwrap();
                     *is_started = false;
                 }
@@ -316,6 +403,117 @@ impl ThievingGuiState {
                 |path| Message::LoadConfig(path)
                 )
             }
+            Message::OpenCompareDialog => {
+                iced::Command::perform(async {
+                    FileDialog::new()
+                        .set_location(DEFAULT_SAVE_DIR)
+                        .add_filter("saves", &[SAVE_FILE_EXTENSION])
+                        .show_open_single_file().unwrap()
+                },
+                |path| Message::CompareWith(path)
+                )
+            }
+            Message::CompareWith(path) => {
+                let other = match path.as_ref().map(ThievingConfigState::load_from_file) {
+                    Some(Ok(other)) => other,
+                    _ => return iced::Command::none(),
+                };
+                let config = self.config_stat.config.clone();
+                let iterations = self.config_stat.sims_count as usize;
+                self.sim_result = text_editor::Content::with_text("Comparing...");
+                iced::Command::perform(
+                    async move {
+                        compare(&config, &other.config, iterations, thieving::sim_with_rng)
+                    },
+                    |r| Message::CompareComplete(r)
+                )
+            }
+            Message::CompareComplete(comparisons) => {
+                self.sim_result = text_editor::Content::with_text(
+                    &format!("A: current config, B: loaded config\n\n{}", format_comparison_results(comparisons))
+                );
+                iced::Command::none()
+            }
+            Message::RunPickSelected(pick) => {
+                self.run_pick = *pick;
+                iced::Command::none()
+            }
+            Message::ShowEnvelope(show) => {
+                self.show_envelope = *show;
+                iced::Command::none()
+            }
+            Message::ShowTimeline => {
+                // The picked run is found in a seeded batch, then replayed with tracing on
+                let config = self.config_stat.config.clone();
+                let metric = self.metric;
+                let pick = self.run_pick;
+                let iterations = self.config_stat.sims_count as usize;
+                iced::Command::perform(
+                    async move {
+                        let seed = pick_seed(iterations, pick, |rng| thieving::sim_with_rng(&config, rng).metric(metric));
+                        let (_, trace) = thieving::sim_traced(&config, &mut StdRng::seed_from_u64(seed));
+                        (seed, trace)
+                    },
+                    |(seed, trace)| Message::TimelineComplete(seed, trace)
+                )
+            }
+            Message::TimelineComplete(seed, trace) => {
+                self.sim_result = text_editor::Content::with_text(
+                    &format!("{} run by {}, seed {}\n\n{}", self.run_pick, self.metric, seed, format_trace(trace))
+                );
+                self.chart = Chart::from_trace(trace);
+                iced::Command::none()
+            }
+            Message::SweepAxis1(axis) => {
+                self.sweep_axes[0] = axis.clone();
+                iced::Command::none()
+            }
+            Message::SweepAxis2(axis) => {
+                self.sweep_axes[1] = axis.clone();
+                iced::Command::none()
+            }
+            Message::MetricSelected(metric) => {
+                self.metric = *metric;
+                iced::Command::none()
+            }
+            Message::StartSweep => {
+                let axes = self.sweep_axes.iter()
+                    .filter(|axis| !axis.trim().is_empty())
+                    .map(|axis| axis.trim().parse())
+                    .collect::<Result<Vec<SweepAxis>, String>>();
+                let axes = match axes {
+                    Ok(axes) => axes,
+                    Err(e) => {
+                        self.sim_result = text_editor::Content::with_text(&e);
+                        return iced::Command::none();
+                    }
+                };
+                self.is_sweeping = true;
+                let config = self.config_stat.config.clone();
+                let metric = self.metric;
+                let iterations = self.config_stat.sims_count as usize;
+                iced::Command::perform(
+                    async move {
+                        sweep(&config, &axes, metric, iterations, thieving::sim).map(|points| (axes, points))
+                    },
+                    |r| Message::SweepComplete(r)
+                )
+            }
+            Message::SweepComplete(result) => {
+                self.is_sweeping = false;
+                match result {
+                    Ok((axes, points)) => {
+                        self.sim_result = text_editor::Content::with_text(
+                            &format_sweep_table(axes, self.metric, points)
+                        );
+                        self.chart = Chart::from_sweep(axes, points);
+                    }
+                    Err(e) => {
+                        self.sim_result = text_editor::Content::with_text(e);
+                    }
+                }
+                iced::Command::none()
+            }
             _ => {
                 self.config_stat.update(message);
                 println!("{:?}", &self.config_stat.config);
@@ -334,6 +532,21 @@ impl ThievingGuiState {
             ].height(Length::Shrink).spacing(5),
             Space::with_height(Length::Fixed(10.0)),
             progress_bar(0.0..=self.config_stat.sims_count as f32, self.progress as f32).width(Length::Fill), // Updated arguments
+            Space::with_height(Length::Fixed(10.0)),
+            row![
+                text_input("field=from:to:steps", &self.sweep_axes[0]).on_input(Message::SweepAxis1),
+                text_input("field=from:to:steps", &self.sweep_axes[1]).on_input(Message::SweepAxis2),
+                pick_list(&Metric::ALL[..], Some(self.metric), Message::MetricSelected).width(Length::Fixed(100.0)),
+                if self.is_sweeping {button("Sweeping...")} else {button("Sweep").on_press(Message::StartSweep)},
+            ].spacing(5).align_items(iced::alignment::Alignment::Center),
+            Space::with_height(Length::Fixed(5.0)),
+            row![
+                text("Run: "),
+                pick_list(&RunPick::ALL[..], Some(self.run_pick), Message::RunPickSelected).width(Length::Fixed(100.0)),
+                button("Timeline").on_press(Message::ShowTimeline),
+                checkbox("Health band", self.show_envelope).on_toggle(Message::ShowEnvelope),
+            ].spacing(5).align_items(iced::alignment::Alignment::Center),
+            Canvas::new(&self.chart).width(Length::Fill).height(Length::Fixed(220.0)),
             Space::with_height(Length::Fill),
             row![
                  if *is_started {button("Stop simulation").on_press(Message::StopSim)} else {button("Start simulation").on_press(Message::StartSim)},
@@ -341,6 +554,8 @@ impl ThievingGuiState {
                  button("Save").on_press(Message::OpenSaveConfigDialog),
                  Space::with_width(Length::Fixed(5.0)),
                  button("Load").on_press(Message::OpenLoadConfigDialog),
+                 Space::with_width(Length::Fixed(5.0)),
+                 button("Compare").on_press(Message::OpenCompareDialog),
              ],
         ].padding(15)
     }
@@ -0,0 +1,232 @@
+use iced::widget::canvas::{self, Frame, Geometry, Path, Stroke, Text};
+use iced::{mouse, Color, Pixels, Point, Rectangle, Renderer, Size, Theme};
+use sim::envelope::Checkpoint;
+use sim::sweep::{SweepAxis, SweepPoint};
+use sim::trace::{EventKind, Trace};
+
+const MARGIN: f32 = 40.0;
+const LABEL_COLOR: Color = Color::from_rgb(0.5, 0.5, 0.5);
+const STUN_COLOR: Color = Color::from_rgb(1.0, 0.6, 0.0);
+const EAT_COLOR: Color = Color::from_rgb(0.2, 0.4, 0.9);
+const KILL_COLOR: Color = Color::from_rgb(0.6, 0.2, 0.8);
+const DEATH_COLOR: Color = Color::from_rgb(0.9, 0.1, 0.1);
+
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub points: Vec<(f64, f64)>,
+    pub color: Color,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub point: (f64, f64),
+    pub color: Color,
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub enum Chart {
+    #[default]
+    Empty,
+    Lines(Vec<Line>),
+    // Rows go along the y axis, columns along the x axis
+    Heatmap(Vec<Vec<f64>>),
+    // Health over time of a single run
+    Timeline(Line, Vec<Marker>),
+    // Health percentiles over time across a batch
+    Band(Vec<Checkpoint>),
+}
+
+impl Chart {
+    pub fn from_sweep(axes: &[SweepAxis], points: &[SweepPoint]) -> Self {
+        match axes.len() {
+            1 => {
+                let line = |f: fn(&SweepPoint) -> f64, color| Line {
+                    points: points.iter().map(|p| (p.values[0], f(p))).collect(),
+                    color,
+                };
+                Chart::Lines(vec![
+                    line(|p| p.estimate.ci95().0, LABEL_COLOR),
+                    line(|p| p.estimate.ci95().1, LABEL_COLOR),
+                    line(|p| p.estimate.mean, Color::from_rgb(0.2, 0.4, 0.9)),
+                ])
+            }
+            2 => {
+                let columns = axes[1].values().len();
+                Chart::Heatmap(points.chunks(columns).map(|row| row.iter().map(|p| p.estimate.mean).collect()).collect())
+            }
+            _ => Chart::Empty,
+        }
+    }
+
+    pub fn from_trace(trace: &Trace) -> Self {
+        let health = Line {
+            points: trace.events.iter().map(|e| (e.time, e.health.max(0) as f64)).collect(),
+            color: Color::from_rgb(0.2, 0.7, 0.3),
+        };
+        let markers = trace.events.iter().filter_map(|e| {
+            let color = match e.kind {
+                EventKind::Stun { .. } => STUN_COLOR,
+                EventKind::Eat { .. } => EAT_COLOR,
+                EventKind::Kill { .. } => KILL_COLOR,
+                EventKind::Death => DEATH_COLOR,
+                _ => return None,
+            };
+            Some(Marker { point: (e.time, e.health.max(0) as f64), color })
+        }).collect();
+        Chart::Timeline(health, markers)
+    }
+}
+
+
+fn range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (min, max) = values.fold((f64::MAX, f64::MIN), |(min, max), v| (min.min(v), max.max(v)));
+    if min >= max { (min - 1.0, min + 1.0) } else { (min, max) }
+}
+
+fn label(frame: &mut Frame, content: String, position: Point) {
+    frame.fill_text(Text {
+        content,
+        position,
+        color: LABEL_COLOR,
+        size: Pixels(12.0),
+        ..Text::default()
+    });
+}
+
+// Maps data coordinates to the drawing area inside the margins
+struct Scale {
+    x: (f64, f64),
+    y: (f64, f64),
+    width: f32,
+    height: f32,
+}
+
+impl Scale {
+    fn new<'a>(points: impl Iterator<Item = &'a (f64, f64)> + Clone, size: Size) -> Self {
+        Self {
+            x: range(points.clone().map(|p| p.0)),
+            y: range(points.map(|p| p.1)),
+            width: size.width - MARGIN * 2.0,
+            height: size.height - MARGIN * 2.0,
+        }
+    }
+
+    fn point(&self, (x, y): (f64, f64)) -> Point {
+        Point::new(
+            MARGIN + ((x - self.x.0) / (self.x.1 - self.x.0)) as f32 * self.width,
+            MARGIN + self.height - ((y - self.y.0) / (self.y.1 - self.y.0)) as f32 * self.height,
+        )
+    }
+
+    fn label_axes(&self, frame: &mut Frame) {
+        label(frame, format!("{:.2}", self.y.1), Point::new(0.0, MARGIN - 14.0));
+        label(frame, format!("{:.2}", self.y.0), Point::new(0.0, MARGIN + self.height));
+        label(frame, format!("{:.2}", self.x.0), Point::new(MARGIN, MARGIN + self.height + 4.0));
+        label(frame, format!("{:.2}", self.x.1), Point::new(MARGIN + self.width - 30.0, MARGIN + self.height + 4.0));
+    }
+}
+
+fn draw_lines(frame: &mut Frame, lines: &[Line], scale: &Scale) {
+    for line in lines.iter().filter(|l| !l.points.is_empty()) {
+        let path = Path::new(|builder| {
+            builder.move_to(scale.point(line.points[0]));
+            for point in &line.points[1..] {
+                builder.line_to(scale.point(*point));
+            }
+        });
+        frame.stroke(&path, Stroke::default().with_color(line.color).with_width(2.0));
+    }
+    scale.label_axes(frame);
+}
+
+fn draw_band(frame: &mut Frame, checkpoints: &[Checkpoint], size: Size) {
+    let points: Vec<(f64, f64)> = checkpoints.iter().map(|c| (c.time, c.p95)).chain([(0.0, 0.0)]).collect();
+    let scale = Scale::new(points.iter(), size);
+    let band = Path::new(|builder| {
+        builder.move_to(scale.point((checkpoints[0].time, checkpoints[0].p95)));
+        for c in &checkpoints[1..] {
+            builder.line_to(scale.point((c.time, c.p95)));
+        }
+        for c in checkpoints.iter().rev() {
+            builder.line_to(scale.point((c.time, c.p5)));
+        }
+        builder.close();
+    });
+    frame.fill(&band, Color::from_rgba(0.2, 0.7, 0.3, 0.3));
+    let line = |f: fn(&Checkpoint) -> f64, color| Line {
+        points: checkpoints.iter().map(|c| (c.time, f(c))).collect(),
+        color,
+    };
+    draw_lines(frame, &[line(|c| c.mean, LABEL_COLOR), line(|c| c.p50, Color::from_rgb(0.2, 0.7, 0.3))], &scale);
+    label(frame, "p5 - p95, median and mean health".to_string(), Point::new(MARGIN, 4.0));
+}
+
+fn draw_timeline(frame: &mut Frame, health: &Line, markers: &[Marker], size: Size) {
+    // Health axis always starts at zero so it shows how close the run came to dying
+    let scale = Scale::new(health.points.iter().chain(&[(0.0, 0.0)]), size);
+    draw_lines(frame, std::slice::from_ref(health), &scale);
+    for marker in markers {
+        frame.fill(&Path::circle(scale.point(marker.point), 3.0), marker.color);
+    }
+    let legend = [("stun", STUN_COLOR), ("eat", EAT_COLOR), ("kill", KILL_COLOR), ("death", DEATH_COLOR)];
+    for (i, (name, color)) in legend.iter().enumerate() {
+        frame.fill_text(Text {
+            content: name.to_string(),
+            position: Point::new(MARGIN + 50.0 * i as f32, 4.0),
+            color: *color,
+            size: Pixels(12.0),
+            ..Text::default()
+        });
+    }
+}
+
+fn draw_heatmap(frame: &mut Frame, rows: &[Vec<f64>], size: Size) {
+    let (min, max) = range(rows.iter().flatten().copied());
+    let (width, height) = (size.width - MARGIN * 2.0, size.height - MARGIN * 2.0);
+    let cell_height = height / rows.len() as f32;
+    for (i, row) in rows.iter().enumerate() {
+        let cell_width = width / row.len() as f32;
+        for (j, value) in row.iter().enumerate() {
+            // Blue for the lowest value through to red for the highest
+            let t = ((value - min) / (max - min)) as f32;
+            frame.fill_rectangle(
+                Point::new(MARGIN + j as f32 * cell_width, MARGIN + height - (i + 1) as f32 * cell_height),
+                Size::new(cell_width, cell_height),
+                Color::from_rgb(t, 0.2, 1.0 - t),
+            );
+        }
+    }
+    label(frame, format!("min {:.2}, max {:.2}", min, max), Point::new(MARGIN, MARGIN + height + 4.0));
+}
+
+
+impl<Message> canvas::Program<Message> for Chart {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        match self {
+            Chart::Empty => {}
+            Chart::Lines(lines) => {
+                let scale = Scale::new(lines.iter().flat_map(|l| l.points.iter()), bounds.size());
+                draw_lines(&mut frame, lines, &scale);
+            }
+            Chart::Timeline(health, markers) => draw_timeline(&mut frame, health, markers, bounds.size()),
+            Chart::Band(checkpoints) if !checkpoints.is_empty() => draw_band(&mut frame, checkpoints, bounds.size()),
+            Chart::Band(_) => {}
+            Chart::Heatmap(rows) if !rows.is_empty() => draw_heatmap(&mut frame, rows, bounds.size()),
+            Chart::Heatmap(_) => {}
+        }
+        vec![frame.into_geometry()]
+    }
+}
@@ -0,0 +1,355 @@
+use std::cmp;
+use std::path::Path;
+use indoc::indoc;
+use rand::Rng;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use statistical::mean;
+use super::batch::{per_hour, Metric, Metrics};
+use super::food::{Food, FoodState};
+use super::format_duration_as_hms;
+use super::profit::{add_item, Items, Profit};
+
+pub const MAX_TIME: Decimal = dec!(28800);  // 8 hours
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub name: String,
+    pub time: Decimal,  // in seconds
+    pub xp: i32,
+    #[serde(default)]
+    pub gp: i32,  // earned on every completion
+    #[serde(default)]
+    pub gp_cost: i32,  // paid on every attempt
+    #[serde(default)]
+    pub fail_chance: f32,
+    #[serde(default)]
+    pub fail_damage_min: i32,
+    #[serde(default)]
+    pub fail_damage_max: i32,
+}
+
+
+// Bonuses of the built pillars and other passives, applied to every obstacle
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PassiveBonus {
+    pub interval_reduction: f32,  // 0.1 is 10% faster
+    pub xp_bonus: f32,  // 0.1 is +10% xp
+    pub gp_bonus: f32,  // 0.1 is +10% gp
+    pub cost_reduction: f32,  // 0.1 is 10% cheaper
+    pub fail_chance_reduction: f32,  // taken off the failure chance
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AgilitySimConfig {
+    pub health_regeneration_interval: Decimal, // in seconds
+    pub health_regeneration_amount: i32,
+    pub max_health: i32,
+    pub obstacles: Vec<Obstacle>,  // run in order, a lap ends after the last one
+    #[serde(default)]
+    pub passives: PassiveBonus,
+    #[serde(default)]
+    pub food: Option<Food>,
+    #[serde(default)]
+    pub start_health: Option<i32>,  // max health if not set
+    #[serde(default)]
+    pub max_time: Option<Decimal>,  // MAX_TIME if not set
+}
+
+impl AgilitySimConfig {
+    pub fn new(health_regeneration_interval: Decimal, health_regeneration_amount: i32, max_health: i32, obstacles: Vec<Obstacle>) -> Self {
+        Self {
+            health_regeneration_interval,
+            health_regeneration_amount,
+            max_health,
+            obstacles,
+            passives: PassiveBonus::default(),
+            food: None,
+            start_health: None,
+            max_time: None,
+        }
+    }
+
+    pub fn max_time(&self) -> Decimal {
+        self.max_time.unwrap_or(MAX_TIME)
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let json = std::fs::read(path)?;
+        let config: AgilitySimConfig = serde_json::from_slice(&json)?;
+        Ok(config)
+    }
+
+    fn interval(&self, obstacle: &Obstacle) -> Decimal {
+        let speed = Decimal::try_from(1.0 - self.passives.interval_reduction).unwrap_or(Decimal::ONE);
+        (obstacle.time * speed).round_dp(1).max(dec!(0.1))
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct AgilitySimResult {
+    pub time: u32,
+    pub laps: u32,
+    pub obstacles_completed: u32,
+    pub failures: u32,
+    pub failure_damage: u32,
+    pub xp: f64,
+    pub gp_earned: f64,
+    pub gp_spent: f64,
+    pub died: bool,
+    pub consumed: Items,
+    pub loot: Items,
+}
+
+impl Profit for AgilitySimResult {
+    fn time(&self) -> f64 {
+        self.time as f64
+    }
+
+    fn gold(&self) -> f64 {
+        self.gp_earned - self.gp_spent
+    }
+
+    fn loot(&self) -> &Items {
+        &self.loot
+    }
+
+    fn consumed(&self) -> &Items {
+        &self.consumed
+    }
+}
+
+impl Metrics for AgilitySimResult {
+    fn metric(&self, metric: Metric) -> f64 {
+        let time = self.time as f64;
+        match metric {
+            Metric::Time => time,
+            Metric::GoldPerHour => per_hour(self.gold(), time),
+            Metric::KillsPerHour => per_hour(self.laps as f64, time),
+            Metric::XpPerHour => per_hour(self.xp, time),
+            Metric::Survival => if self.died { 0.0 } else { 1.0 },
+        }
+    }
+}
+
+
+pub fn sim(config: &AgilitySimConfig) -> AgilitySimResult {
+    sim_with_rng(config, &mut rand::thread_rng())
+}
+
+// Same as `sim`, with the random source passed in so runs can be seeded
+pub fn sim_with_rng<R: Rng>(config: &AgilitySimConfig, rng: &mut R) -> AgilitySimResult {
+    let mut result = AgilitySimResult::default();
+    let mut current_health = config.start_health.map_or(config.max_health, |h| cmp::min(h, config.max_health));
+    let mut food = FoodState::new(config.food.as_ref());
+    let mut regen_timer = dec!(0);
+    let mut time = dec!(0);
+    let mut next = 0;
+
+    let passives = &config.passives;
+    let max_time = config.max_time();
+    while !config.obstacles.is_empty() && current_health > 0 {
+        let obstacle = &config.obstacles[next];
+        let interval = config.interval(obstacle);
+        if time + interval > max_time {
+            time = max_time;
+            break;
+        }
+        time += interval;
+        result.gp_spent += obstacle.gp_cost as f64 * (1.0 - passives.cost_reduction as f64);
+
+        let fail_chance = obstacle.fail_chance - passives.fail_chance_reduction;
+        if rng.gen::<f32>() < fail_chance {
+            // Failed obstacles are attempted again
+            result.failures += 1;
+            let damage = rng.gen_range(obstacle.fail_damage_min..=obstacle.fail_damage_max.max(obstacle.fail_damage_min));
+            result.failure_damage += damage as u32;
+            current_health -= damage;
+            if current_health <= 0 {
+                break;
+            }
+            current_health += food.eat(config.food.as_ref(), current_health as u32, config.max_health as u32) as i32;
+        } else {
+            result.obstacles_completed += 1;
+            result.xp += obstacle.xp as f64 * (1.0 + passives.xp_bonus as f64);
+            result.gp_earned += obstacle.gp as f64 * (1.0 + passives.gp_bonus as f64);
+            next = (next + 1) % config.obstacles.len();
+            if next == 0 {
+                result.laps += 1;
+            }
+        }
+
+        // Health regenerates while running the course
+        regen_timer += interval;
+        while regen_timer >= config.health_regeneration_interval && config.health_regeneration_interval > dec!(0) {
+            regen_timer -= config.health_regeneration_interval;
+            current_health = cmp::min(current_health + config.health_regeneration_amount, config.max_health);
+        }
+    }
+
+    result.time = time.to_u32().unwrap_or_default();
+    result.died = current_health <= 0;
+    if let Some(f) = &config.food {
+        add_item(&mut result.consumed, &f.name, food.eaten);
+    }
+    result
+}
+
+
+pub fn format_agility_results(results: &[AgilitySimResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    let mut time: Vec<f64> = Vec::new();
+    let mut laps_sum = 0.0;
+    let mut xp_sum = 0.0;
+    let mut gp_earned_sum = 0.0;
+    let mut gp_spent_sum = 0.0;
+    let mut failures_sum = 0.0;
+    let mut damage_sum = 0.0;
+    let mut deaths = 0.0;
+
+    for r in results {
+        time.push(r.time as f64);
+        laps_sum += r.laps as f64;
+        xp_sum += r.xp;
+        gp_earned_sum += r.gp_earned;
+        gp_spent_sum += r.gp_spent;
+        failures_sum += r.failures as f64;
+        damage_sum += r.failure_damage as f64;
+        if r.died {
+            deaths += 1.0;
+        }
+    }
+    let hours = time.iter().sum::<f64>() / 3600.0;
+    let per_hour = |value: f64| if hours > 0.0 { value / hours } else { 0.0 };
+    let damage_per_failure = if failures_sum > 0.0 { damage_sum / failures_sum } else { 0.0 };
+
+    format!(
+        indoc!(r#"
+        Mean time: {}
+        Died: {:.2}%
+        ------------------------
+        Mean laps: {:.2}
+        Laps per hour: {:.2}
+        XP per hour: {:.2}
+        ------------------------
+        GP earned per hour: {:.2}
+        GP spent per hour: {:.2}
+        GP per hour: {:.2}
+        ------------------------
+        Failures per hour: {:.2}
+        Failure damage per hour: {:.2}
+        Mean damage per failure: {:.2}
+        "#),
+        format_duration_as_hms(mean(&time)),
+        deaths / results.len() as f64 * 100.0,
+        laps_sum / results.len() as f64,
+        per_hour(laps_sum),
+        per_hour(xp_sum),
+        per_hour(gp_earned_sum),
+        per_hour(gp_spent_sum),
+        per_hour(gp_earned_sum - gp_spent_sum),
+        per_hour(failures_sum),
+        per_hour(damage_sum),
+        damage_per_failure,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::*;
+
+    fn obstacle(name: &str, time: Decimal) -> Obstacle {
+        Obstacle {
+            name: name.to_string(),
+            time,
+            xp: 10,
+            gp: 20,
+            gp_cost: 5,
+            ..Obstacle::default()
+        }
+    }
+
+    fn config(obstacles: Vec<Obstacle>) -> AgilitySimConfig {
+        let mut config = AgilitySimConfig::new(dec!(10), 1, 100, obstacles);
+        config.max_time = Some(dec!(50));
+        config
+    }
+
+    fn run(config: &AgilitySimConfig) -> AgilitySimResult {
+        sim_with_rng(config, &mut StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn laps_take_the_sum_of_the_obstacle_times() {
+        let result = run(&config(vec![obstacle("Log", dec!(3)), obstacle("Rope", dec!(2))]));
+        assert_eq!(result.time, 50);
+        assert_eq!(result.laps, 10);
+        assert_eq!(result.obstacles_completed, 20);
+        assert_eq!(result.xp, 200.0);
+        assert_eq!(result.gp_earned, 400.0);
+        assert_eq!(result.gp_spent, 100.0);
+        assert_eq!(result.gold(), 300.0);
+    }
+
+    #[test]
+    fn failures_deal_damage_and_are_attempted_again() {
+        let mut wall = obstacle("Wall", dec!(1));
+        wall.fail_chance = 1.0;
+        wall.fail_damage_min = 10;
+        wall.fail_damage_max = 10;
+        let mut config = config(vec![wall]);
+        config.health_regeneration_amount = 0;
+        let result = run(&config);
+        assert!(result.died);
+        assert_eq!(result.failures, 10);
+        assert_eq!(result.failure_damage, 100);
+        assert_eq!(result.obstacles_completed, 0);
+        // Every attempt is paid for, failed ones included
+        assert_eq!(result.gp_spent, 50.0);
+        assert_eq!(result.time, 10);
+    }
+
+    #[test]
+    fn passives_apply_to_every_obstacle() {
+        let mut wall = obstacle("Wall", dec!(2));
+        wall.fail_chance = 1.0;
+        let mut config = config(vec![wall]);
+        config.passives = PassiveBonus {
+            interval_reduction: 0.5,
+            xp_bonus: 0.5,
+            gp_bonus: 0.5,
+            cost_reduction: 0.2,
+            fail_chance_reduction: 1.0,
+        };
+        let result = run(&config);
+        assert_eq!(result.failures, 0);
+        assert_eq!(result.laps, 50);
+        assert!((result.xp - 750.0).abs() < 1e-3);
+        assert!((result.gp_earned - 1500.0).abs() < 1e-3);
+        assert!((result.gp_spent - 200.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn start_health_is_capped_at_max_health() {
+        let mut wall = obstacle("Wall", dec!(1));
+        wall.fail_chance = 1.0;
+        wall.fail_damage_min = 50;
+        wall.fail_damage_max = 50;
+        let mut config = config(vec![wall]);
+        config.start_health = Some(1000);
+        let result = run(&config);
+        assert!(result.died);
+        assert_eq!(result.failures, 2);
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use indoc::indoc;
+use rand::Rng;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use statistical::mean;
+use super::batch::{per_hour, Metric, Metrics};
+use super::profit::{add_item, Items, Profit};
+use super::{format_duration_as_hms, min_max};
+
+// Guards against recipes that never use up their inputs
+pub const MAX_TIME: Decimal = dec!(604800);  // 1 week
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Recipe {
+    pub output: String,
+    #[serde(default = "one")]
+    pub output_amount: u32,
+    pub inputs: Items,  // used up by every craft
+    pub interval: Decimal,  // in seconds
+    pub xp: i32,  // per successful craft
+    #[serde(default)]
+    pub fail_chance: f32,  // cooking, the inputs are lost
+    #[serde(default)]
+    pub failed_output: Option<String>,  // e.g. burnt food
+}
+
+fn one() -> u32 {
+    1
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ArtisanSimConfig {
+    pub recipes: Vec<Recipe>,  // each one is crafted until its inputs run out, in order
+    pub bank: Items,
+    #[serde(default)]
+    pub preservation_chance: f32,  // chance the inputs are kept
+    #[serde(default)]
+    pub double_chance: f32,  // chance for twice the output
+    #[serde(default)]
+    pub max_time: Option<Decimal>,  // MAX_TIME if not set
+}
+
+impl ArtisanSimConfig {
+    pub fn new(recipes: Vec<Recipe>, bank: Items) -> Self {
+        Self {
+            recipes,
+            bank,
+            preservation_chance: 0.0,
+            double_chance: 0.0,
+            max_time: None,
+        }
+    }
+
+    pub fn max_time(&self) -> Decimal {
+        self.max_time.unwrap_or(MAX_TIME)
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let json = std::fs::read(path)?;
+        let config: ArtisanSimConfig = serde_json::from_slice(&json)?;
+        Ok(config)
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct ArtisanSimResult {
+    pub time: u32,
+    pub crafts: u32,  // attempts, failed ones included
+    pub failures: u32,
+    pub preserved: u32,
+    pub xp: f64,
+    pub out_of_inputs: bool,  // false if the run hit the time cap first
+    pub produced: Items,
+    pub consumed: Items,
+    pub leftover: Items,  // the bank at the end of the run
+}
+
+impl Profit for ArtisanSimResult {
+    fn time(&self) -> f64 {
+        self.time as f64
+    }
+
+    fn gold(&self) -> f64 {
+        0.0
+    }
+
+    fn loot(&self) -> &Items {
+        &self.produced
+    }
+
+    fn consumed(&self) -> &Items {
+        &self.consumed
+    }
+}
+
+impl Metrics for ArtisanSimResult {
+    fn metric(&self, metric: Metric) -> f64 {
+        let time = self.time as f64;
+        match metric {
+            Metric::Time => time,
+            Metric::GoldPerHour => 0.0,
+            Metric::KillsPerHour => per_hour((self.crafts - self.failures) as f64, time),
+            Metric::XpPerHour => per_hour(self.xp, time),
+            Metric::Survival => 1.0,
+        }
+    }
+}
+
+
+fn has_inputs(bank: &Items, recipe: &Recipe) -> bool {
+    !recipe.inputs.is_empty() && recipe.inputs.iter().all(|(item, amount)| bank.get(item).copied().unwrap_or_default() >= *amount)
+}
+
+
+pub fn sim(config: &ArtisanSimConfig) -> ArtisanSimResult {
+    sim_with_rng(config, &mut rand::thread_rng())
+}
+
+// Same as `sim`, with the random source passed in so runs can be seeded
+pub fn sim_with_rng<R: Rng>(config: &ArtisanSimConfig, rng: &mut R) -> ArtisanSimResult {
+    let mut result = ArtisanSimResult::default();
+    let mut bank = config.bank.clone();
+    let mut time = dec!(0);
+    let max_time = config.max_time();
+
+    'recipes: for recipe in &config.recipes {
+        let interval = recipe.interval.max(dec!(0.1));
+        while has_inputs(&bank, recipe) {
+            if time + interval > max_time {
+                time = max_time;
+                break 'recipes;
+            }
+            time += interval;
+            result.crafts += 1;
+
+            if rng.gen::<f32>() < config.preservation_chance {
+                result.preserved += 1;
+            } else {
+                for (item, amount) in &recipe.inputs {
+                    if let Some(stock) = bank.get_mut(item) {
+                        *stock -= amount;
+                    }
+                    add_item(&mut result.consumed, item, *amount);
+                }
+            }
+
+            // Outputs go back to the bank so later recipes can use them
+            if rng.gen::<f32>() < recipe.fail_chance {
+                result.failures += 1;
+                if let Some(item) = &recipe.failed_output {
+                    add_item(&mut bank, item, 1);
+                    add_item(&mut result.produced, item, 1);
+                }
+            } else {
+                let mut amount = recipe.output_amount;
+                if rng.gen::<f32>() < config.double_chance {
+                    amount *= 2;
+                }
+                add_item(&mut bank, &recipe.output, amount);
+                add_item(&mut result.produced, &recipe.output, amount);
+                result.xp += recipe.xp as f64;
+            }
+        }
+    }
+
+    result.out_of_inputs = !config.recipes.iter().any(|r| has_inputs(&bank, r));
+    bank.retain(|_, amount| *amount > 0);
+    result.time = time.to_u32().unwrap_or_default();
+    result.leftover = bank;
+    result
+}
+
+
+fn format_items(results: &[ArtisanSimResult], items: fn(&ArtisanSimResult) -> &Items) -> String {
+    let names: BTreeSet<&String> = results.iter().flat_map(|r| items(r).keys()).collect();
+    let mut output = String::new();
+    for name in names {
+        let amounts: Vec<f64> = results.iter().map(|r| items(r).get(name).copied().unwrap_or_default() as f64).collect();
+        output += &format!("{}: {:.2}\n", name, mean(&amounts));
+    }
+    output
+}
+
+
+pub fn format_artisan_results(results: &[ArtisanSimResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    let mut time: Vec<f64> = Vec::new();
+    let mut crafts: Vec<f64> = Vec::new();
+    let mut failures_sum = 0.0;
+    let mut preserved_sum = 0.0;
+    let mut xp_sum = 0.0;
+    let mut out_of_inputs = 0.0;
+
+    for r in results {
+        time.push(r.time as f64);
+        crafts.push(r.crafts as f64);
+        failures_sum += r.failures as f64;
+        preserved_sum += r.preserved as f64;
+        xp_sum += r.xp;
+        if r.out_of_inputs {
+            out_of_inputs += 1.0;
+        }
+    }
+    let count = results.len() as f64;
+    let (min_time, max_time) = min_max(&time);
+
+    format!(
+        indoc!(r#"
+        Mean session length: {}
+        Min session length: {}
+        Max session length: {}
+        Ran out of inputs: {:.2}%
+        ------------------------
+        Mean crafts: {:.2}
+        Mean failures: {:.2}
+        Mean preserved: {:.2}
+        Mean xp: {:.2}
+        XP per hour: {:.2}
+        ------------------------
+        Mean produced:
+        {}------------------------
+        Mean consumed:
+        {}------------------------
+        Mean leftover:
+        {}"#),
+        format_duration_as_hms(mean(&time)),
+        format_duration_as_hms(min_time),
+        format_duration_as_hms(max_time),
+        out_of_inputs / count * 100.0,
+        mean(&crafts),
+        failures_sum / count,
+        preserved_sum / count,
+        xp_sum / count,
+        per_hour(xp_sum, time.iter().sum::<f64>()),
+        format_items(results, |r| &r.produced),
+        format_items(results, |r| &r.consumed),
+        format_items(results, |r| &r.leftover),
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::*;
+
+    fn recipe(output: &str, input: &str) -> Recipe {
+        Recipe {
+            output: output.to_string(),
+            output_amount: 1,
+            inputs: Items::from([(input.to_string(), 1)]),
+            interval: dec!(2),
+            xp: 10,
+            fail_chance: 0.0,
+            failed_output: None,
+        }
+    }
+
+    fn config(recipes: Vec<Recipe>) -> ArtisanSimConfig {
+        ArtisanSimConfig::new(recipes, Items::from([("Raw".to_string(), 10), ("Salt".to_string(), 3)]))
+    }
+
+    fn run(config: &ArtisanSimConfig) -> ArtisanSimResult {
+        sim_with_rng(config, &mut StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn crafts_until_the_inputs_run_out() {
+        let result = run(&config(vec![recipe("Cooked", "Raw")]));
+        assert!(result.out_of_inputs);
+        assert_eq!(result.time, 20);
+        assert_eq!(result.crafts, 10);
+        assert_eq!(result.xp, 100.0);
+        assert_eq!(result.consumed, Items::from([("Raw".to_string(), 10)]));
+        assert_eq!(result.produced, Items::from([("Cooked".to_string(), 10)]));
+        assert_eq!(result.leftover, Items::from([("Cooked".to_string(), 10), ("Salt".to_string(), 3)]));
+    }
+
+    #[test]
+    fn later_recipes_use_the_earlier_outputs() {
+        let result = run(&config(vec![recipe("Cooked", "Raw"), recipe("Salted", "Cooked")]));
+        assert_eq!(result.crafts, 20);
+        assert_eq!(result.leftover, Items::from([("Salted".to_string(), 10), ("Salt".to_string(), 3)]));
+    }
+
+    #[test]
+    fn preserved_inputs_are_kept() {
+        let mut config = config(vec![recipe("Cooked", "Raw")]);
+        config.preservation_chance = 1.0;
+        config.max_time = Some(dec!(21));
+        let result = run(&config);
+        assert!(!result.out_of_inputs);
+        assert_eq!(result.time, 21);
+        assert_eq!(result.preserved, 10);
+        assert!(result.consumed.is_empty());
+        assert_eq!(result.leftover["Raw"], 10);
+    }
+
+    #[test]
+    fn double_output_doubles_the_products() {
+        let mut config = config(vec![recipe("Cooked", "Raw")]);
+        config.double_chance = 1.0;
+        let result = run(&config);
+        assert_eq!(result.produced["Cooked"], 20);
+        assert_eq!(result.xp, 100.0);
+    }
+
+    #[test]
+    fn failures_lose_the_inputs_and_give_the_failed_output() {
+        let mut burnt = recipe("Cooked", "Raw");
+        burnt.fail_chance = 1.0;
+        burnt.failed_output = Some("Burnt".to_string());
+        let result = run(&config(vec![burnt]));
+        assert_eq!(result.failures, 10);
+        assert_eq!(result.xp, 0.0);
+        assert_eq!(result.consumed["Raw"], 10);
+        assert_eq!(result.produced, Items::from([("Burnt".to_string(), 10)]));
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Metric {
+    Time,
+    GoldPerHour,
+    KillsPerHour,  // successful steals, agility laps, gathered resources or crafts outside of combat
+    XpPerHour,
+    Survival,  // 1 if the run did not end with a death
+}
+
+impl Metric {
+    pub const ALL: [Metric; 5] = [
+        Metric::Time,
+        Metric::GoldPerHour,
+        Metric::KillsPerHour,
+        Metric::XpPerHour,
+        Metric::Survival,
+    ];
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Metric::Time => "time",
+            Metric::GoldPerHour => "gold",
+            Metric::KillsPerHour => "kills",
+            Metric::XpPerHour => "xp",
+            Metric::Survival => "survival",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Metric::ALL.iter()
+            .find(|m| m.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown metric: {}, expected one of time, gold, kills, xp, survival", s))
+    }
+}
+
+
+pub trait Metrics {
+    fn metric(&self, metric: Metric) -> f64;
+}
+
+pub fn per_hour(value: f64, time: f64) -> f64 {
+    if time > 0.0 { value / time * 3600.0 } else { 0.0 }
+}
+
+
+// Mean of a batch with its standard error
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Estimate {
+    pub mean: f64,
+    pub std_error: f64,
+    pub count: usize,
+}
+
+impl Estimate {
+    pub fn new(values: &[f64]) -> Self {
+        let count = values.len();
+        if count == 0 {
+            return Self::default();
+        }
+        let mean = values.iter().sum::<f64>() / count as f64;
+        if count == 1 {
+            return Self { mean, std_error: 0.0, count };
+        }
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64;
+        Self {
+            mean,
+            std_error: (variance / count as f64).sqrt(),
+            count,
+        }
+    }
+
+    pub fn from_results<R: Metrics>(results: &[R], metric: Metric) -> Self {
+        Self::new(&results.iter().map(|r| r.metric(metric)).collect::<Vec<_>>())
+    }
+
+    // 95% confidence interval
+    pub fn ci95(&self) -> (f64, f64) {
+        (self.mean - 1.96 * self.std_error, self.mean + 1.96 * self.std_error)
+    }
+
+    // Two sided p-value for the mean being zero, using the normal approximation
+    pub fn p_value(&self) -> f64 {
+        if self.std_error == 0.0 {
+            return if self.mean == 0.0 { 1.0 } else { 0.0 };
+        }
+        erfc((self.mean / self.std_error).abs() / std::f64::consts::SQRT_2)
+    }
+}
+
+// Complementary error function, Abramowitz and Stegun 7.1.26
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    poly * (-x * x).exp()
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (low, high) = self.ci95();
+        write!(f, "{:.2} ({:.2} - {:.2})", self.mean, low, high)
+    }
+}
+
+
+pub fn run_batch<R, F: FnMut() -> R>(iterations: usize, mut sim: F) -> Vec<R> {
+    (0..iterations).map(|_| sim()).collect()
+}
+
+
+// How small the standard error of the mean has to get
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Tolerance {
+    Absolute(f64),
+    Relative(f64),  // fraction of the mean
+}
+
+// Parses `500` as absolute and `1%` as relative
+impl FromStr for Tolerance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |_| format!("Invalid target error: {}, expected e.g. 500 or 1%", s);
+        match s.trim().strip_suffix('%') {
+            Some(percent) => Ok(Tolerance::Relative(percent.parse::<f64>().map_err(error)? / 100.0)),
+            None => Ok(Tolerance::Absolute(s.trim().parse().map_err(error)?)),
+        }
+    }
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct Convergence {
+    pub metric: Metric,
+    pub tolerance: Tolerance,
+    pub min_iterations: usize,
+    pub max_iterations: usize,
+}
+
+impl Convergence {
+    // Runs between two checks of the standard error
+    pub const CHECK_INTERVAL: usize = 100;
+
+    pub fn new(metric: Metric, tolerance: Tolerance) -> Self {
+        Self {
+            metric,
+            tolerance,
+            min_iterations: 100,
+            max_iterations: 100_000,
+        }
+    }
+
+    // Reads `--target-error=`, `--metric=`, `--min-iterations=` and `--max-iterations=`.
+    // Returns None when no target error is given.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let value = |name: &str| args.iter().find_map(|a| a.strip_prefix(&format!("--{}=", name)));
+        let tolerance = match value("target-error") {
+            Some(tolerance) => tolerance.parse()?,
+            None => return Ok(None),
+        };
+        let metric = value("metric").unwrap_or("gold").parse()?;
+        let mut convergence = Self::new(metric, tolerance);
+        if let Some(min) = value("min-iterations") {
+            convergence.min_iterations = min.parse().map_err(|_| format!("Invalid min iterations: {}", min))?;
+        }
+        if let Some(max) = value("max-iterations") {
+            convergence.max_iterations = max.parse().map_err(|_| format!("Invalid max iterations: {}", max))?;
+        }
+        Ok(Some(convergence))
+    }
+
+    pub fn is_converged(&self, estimate: &Estimate) -> bool {
+        if estimate.count >= self.max_iterations {
+            return true;
+        }
+        if estimate.count < self.min_iterations {
+            return false;
+        }
+        match self.tolerance {
+            Tolerance::Absolute(error) => estimate.std_error <= error,
+            Tolerance::Relative(fraction) => estimate.std_error <= fraction * estimate.mean.abs(),
+        }
+    }
+}
+
+
+// Runs until the standard error of the metric is within the tolerance or the
+// iteration cap is reached
+pub fn run_until_converged<R: Metrics, F: FnMut() -> R>(convergence: &Convergence, mut sim: F) -> Vec<R> {
+    let mut results = Vec::new();
+    let mut values = Vec::new();
+    loop {
+        let remaining = convergence.max_iterations.saturating_sub(results.len()).max(1);
+        for _ in 0..Convergence::CHECK_INTERVAL.min(remaining) {
+            let result = sim();
+            values.push(result.metric(convergence.metric));
+            results.push(result);
+        }
+        if convergence.is_converged(&Estimate::new(&values)) {
+            return results;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Score(f64);
+
+    impl Metrics for Score {
+        fn metric(&self, _metric: Metric) -> f64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn estimates_the_mean_and_its_error() {
+        let estimate = Estimate::new(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(estimate.mean, 2.5);
+        assert_eq!(estimate.count, 4);
+        assert!((estimate.std_error - (5.0f64 / 3.0 / 4.0).sqrt()).abs() < 1e-12);
+        assert_eq!(Estimate::new(&[7.0]).std_error, 0.0);
+        assert_eq!(Estimate::new(&[]).count, 0);
+    }
+
+    #[test]
+    fn p_values_follow_the_normal_approximation() {
+        let estimate = Estimate { mean: 1.96, std_error: 1.0, count: 100 };
+        assert!((estimate.p_value() - 0.05).abs() < 1e-3);
+        assert_eq!(Estimate { mean: 0.0, std_error: 0.0, count: 1 }.p_value(), 1.0);
+        assert_eq!(Estimate { mean: 1.0, std_error: 0.0, count: 1 }.p_value(), 0.0);
+    }
+
+    #[test]
+    fn parses_tolerances() {
+        assert_eq!("500".parse(), Ok(Tolerance::Absolute(500.0)));
+        assert_eq!("1%".parse(), Ok(Tolerance::Relative(0.01)));
+        assert!("x%".parse::<Tolerance>().is_err());
+    }
+
+    #[test]
+    fn constant_results_stop_at_the_minimum() {
+        let convergence = Convergence::new(Metric::GoldPerHour, Tolerance::Relative(0.01));
+        let results = run_until_converged(&convergence, || Score(10.0));
+        assert_eq!(results.len(), 100);
+    }
+
+    #[test]
+    fn noisy_results_stop_at_the_maximum() {
+        let mut convergence = Convergence::new(Metric::GoldPerHour, Tolerance::Absolute(0.0));
+        convergence.max_iterations = 250;
+        let mut i = 0.0;
+        let results = run_until_converged(&convergence, || {
+            i += 1.0;
+            Score(i % 2.0)
+        });
+        assert_eq!(results.len(), 250);
+    }
+}
@@ -0,0 +1,186 @@
+use indoc::indoc;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use super::batch::{Estimate, Metric, Metrics};
+use super::sweep::{get_field, set_field};
+
+const BISECTION_STEPS: usize = 12;
+
+
+#[derive(Debug, Clone)]
+pub struct BreakEvenConfig {
+    pub field: String,
+    pub low: f64,
+    pub high: f64,
+    pub metric: Metric,
+    pub target: f64,  // the metric mean has to reach it, e.g. survival 0.99
+    pub iterations: usize,  // per bisection step
+    pub replications: usize,  // independent searches, their spread is the uncertainty
+}
+
+
+#[derive(Debug, Clone)]
+pub struct BreakEven {
+    pub config: BreakEvenConfig,
+    // True if the target is reached above the threshold, false if below it
+    pub increasing: bool,
+    pub thresholds: Vec<f64>,
+    pub threshold: Estimate,
+}
+
+
+// Mean of the metric with the field set to the value, along with the value the
+// config really got since integer fields are rounded and Decimal fields are snapped
+// to the 0.1 s grid
+fn evaluate<C, R, F>(config: &C, search: &BreakEvenConfig, value: f64, seed: u64, sim: &F) -> Result<(f64, f64), String>
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let config = set_field(config, &search.field, value)?;
+    let actual = get_field(&config, &search.field)?;
+    let runs: Vec<R> = (0..search.iterations as u64)
+        .map(|i| sim(&config, &mut StdRng::seed_from_u64(seed + i)))
+        .collect();
+    Ok((actual, Estimate::from_results(&runs, search.metric).mean))
+}
+
+
+// One bisection, every step runs with the same seeds so the steps are comparable.
+// There is no exact solver, every step is a batch of sims. `set_field` snaps the
+// values to the field's grid, so the search stops once the bounds are neighbours.
+fn bisect<C, R, F>(config: &C, search: &BreakEvenConfig, seed: u64, sim: &F) -> Result<(f64, bool), String>
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let (mut low, low_mean) = evaluate(config, search, search.low, seed, sim)?;
+    let (mut high, high_mean) = evaluate(config, search, search.high, seed, sim)?;
+    let increasing = match (low_mean >= search.target, high_mean >= search.target) {
+        (false, true) => true,
+        (true, false) => false,
+        _ => return Err(format!(
+            "{} does not cross {} between {} = {} and {}",
+            search.metric, search.target, search.field, search.low, search.high,
+        )),
+    };
+    for _ in 0..BISECTION_STEPS {
+        let (middle, mean) = evaluate(config, search, (low + high) / 2.0, seed, sim)?;
+        if middle == low || middle == high {
+            break;
+        }
+        if (mean >= search.target) == increasing {
+            high = middle;
+        } else {
+            low = middle;
+        }
+    }
+    Ok((if increasing { high } else { low }, increasing))
+}
+
+
+// Finds the field value where the metric reaches the target
+pub fn break_even<C, R, F>(config: &C, search: &BreakEvenConfig, sim: F) -> Result<BreakEven, String>
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let mut thresholds = Vec::new();
+    let mut increasing = true;
+    for _ in 0..search.replications.max(1) {
+        let (threshold, direction) = bisect(config, search, rand::random::<u32>() as u64, &sim)?;
+        thresholds.push(threshold);
+        increasing = direction;
+    }
+    Ok(BreakEven {
+        config: search.clone(),
+        increasing,
+        threshold: Estimate::new(&thresholds),
+        thresholds,
+    })
+}
+
+
+pub fn format_break_even_results(result: &BreakEven) -> String {
+    let search = &result.config;
+    let thresholds: Vec<String> = result.thresholds.iter().map(|t| format!("{:.3}", t)).collect();
+    format!(
+        indoc!(r#"
+        {} for {} >= {}
+        {} {}: {:.3}
+        95% interval: {:.3} - {:.3}
+        Searches: {}
+        "#),
+        search.field,
+        search.metric,
+        search.target,
+        if result.increasing { "Minimum" } else { "Maximum" },
+        search.field,
+        result.threshold.mean,
+        result.threshold.ci95().0,
+        result.threshold.ci95().1,
+        thresholds.join(", "),
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::prelude::ToPrimitive;
+    use rust_decimal_macros::dec;
+    use crate::thieving::ThievingSimConfig;
+    use super::*;
+
+    struct Score(f64);
+
+    impl Metrics for Score {
+        fn metric(&self, _metric: Metric) -> f64 {
+            self.0
+        }
+    }
+
+    fn search(low: f64, high: f64, target: f64) -> BreakEvenConfig {
+        BreakEvenConfig {
+            field: "steal_interval".to_string(),
+            low,
+            high,
+            metric: Metric::Time,
+            target,
+            iterations: 1,
+            replications: 2,
+        }
+    }
+
+    // Ten times the steal interval, so the threshold is known
+    fn sim(config: &ThievingSimConfig, _: &mut StdRng) -> Score {
+        Score((config.steal_interval * dec!(10)).to_f64().unwrap_or_default())
+    }
+
+    fn config() -> ThievingSimConfig {
+        ThievingSimConfig::new(dec!(1), 1, 100, dec!(2), 0.5, 1, 10, 10, 20)
+    }
+
+    #[test]
+    fn finds_the_threshold_on_the_grid() {
+        let result = break_even(&config(), &search(1.0, 5.0, 26.5), sim).unwrap();
+        assert!(result.increasing);
+        assert_eq!(result.thresholds, vec![2.7, 2.7]);
+    }
+
+    #[test]
+    fn decreasing_metrics_return_the_maximum() {
+        let result = break_even(&config(), &search(1.0, 5.0, -26.5), |c, rng| Score(-sim(c, rng).0)).unwrap();
+        assert!(!result.increasing);
+        assert_eq!(result.thresholds, vec![2.6, 2.6]);
+    }
+
+    #[test]
+    fn ranges_that_do_not_cross_the_target_are_errors() {
+        assert!(break_even(&config(), &search(1.0, 2.0, 26.5), sim).is_err());
+    }
+}
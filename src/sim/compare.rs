@@ -0,0 +1,156 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use super::batch::{Estimate, Metric, Metrics};
+
+// Below this many runs the normal approximation behind the p-value is not trusted
+pub const MIN_RUNS: usize = 30;
+const SIGNIFICANCE: f64 = 0.05;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    AHigher,
+    BHigher,
+    NoDifference,
+    // Runs needed to detect the observed difference
+    TooFewRuns(usize),
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct Comparison {
+    pub metric: Metric,
+    pub a: Estimate,
+    pub b: Estimate,
+    pub difference: Estimate,  // b - a, paired by seed
+    pub p_value: f64,
+    pub verdict: Verdict,
+}
+
+impl Comparison {
+    fn new(metric: Metric, a: &[f64], b: &[f64]) -> Self {
+        let differences: Vec<f64> = a.iter().zip(b).map(|(a, b)| b - a).collect();
+        let difference = Estimate::new(&differences);
+        let p_value = difference.p_value();
+
+        // Runs for an 80% chance of detecting the observed difference at 5% significance
+        let std_dev = difference.std_error * (difference.count as f64).sqrt();
+        let needed = (difference.mean != 0.0).then(|| (2.8 * std_dev / difference.mean).powi(2).ceil() as usize);
+
+        let verdict = if difference.count < MIN_RUNS {
+            Verdict::TooFewRuns(needed.unwrap_or_default().max(MIN_RUNS))
+        } else if p_value < SIGNIFICANCE {
+            if difference.mean > 0.0 { Verdict::BHigher } else { Verdict::AHigher }
+        } else {
+            match needed {
+                Some(needed) if needed > difference.count => Verdict::TooFewRuns(needed),
+                _ => Verdict::NoDifference,
+            }
+        };
+        Self {
+            metric,
+            a: Estimate::new(a),
+            b: Estimate::new(b),
+            difference,
+            p_value,
+            verdict,
+        }
+    }
+}
+
+
+// Runs both configs with the same seeds and compares every metric
+pub fn compare<C, R, F>(a: &C, b: &C, iterations: usize, sim: F) -> Vec<Comparison>
+where
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let seed = rand::random::<u32>() as u64;
+    let runs: Vec<(R, R)> = (0..iterations as u64).map(|i| {
+        (sim(a, &mut StdRng::seed_from_u64(seed + i)), sim(b, &mut StdRng::seed_from_u64(seed + i)))
+    }).collect();
+    Metric::ALL.iter().map(|&metric| {
+        let a: Vec<f64> = runs.iter().map(|(a, _)| a.metric(metric)).collect();
+        let b: Vec<f64> = runs.iter().map(|(_, b)| b.metric(metric)).collect();
+        Comparison::new(metric, &a, &b)
+    }).collect()
+}
+
+
+pub fn format_comparison_results(comparisons: &[Comparison]) -> String {
+    let mut output = format!("{:<9} | {:>12} | {:>12} | {:>32} | {:>8} | verdict\n", "metric", "A", "B", "B - A", "p-value");
+    for c in comparisons {
+        let verdict = match c.verdict {
+            Verdict::AHigher => "A is higher".to_string(),
+            Verdict::BHigher => "B is higher".to_string(),
+            Verdict::NoDifference => "no difference".to_string(),
+            Verdict::TooFewRuns(needed) => format!("undecided, needs ~{} runs", needed),
+        };
+        output += &format!(
+            "{:<9} | {:>12.2} | {:>12.2} | {:>32} | {:>8.4} | {}\n",
+            c.metric.to_string(),
+            c.a.mean,
+            c.b.mean,
+            c.difference.to_string(),
+            c.p_value,
+            verdict,
+        );
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use super::*;
+
+    struct Score(f64);
+
+    impl Metrics for Score {
+        fn metric(&self, _metric: Metric) -> f64 {
+            self.0
+        }
+    }
+
+    // The same base for both sides, plus an alternating noise on the difference
+    fn paired(runs: usize, difference: f64, noise: f64) -> (Vec<f64>, Vec<f64>) {
+        let a: Vec<f64> = (0..runs).map(|i| i as f64 * 100.0).collect();
+        let b = a.iter().enumerate().map(|(i, a)| a + difference + if i % 2 == 0 { noise } else { -noise }).collect();
+        (a, b)
+    }
+
+    fn verdict(runs: usize, difference: f64, noise: f64) -> Verdict {
+        let (a, b) = paired(runs, difference, noise);
+        Comparison::new(Metric::GoldPerHour, &a, &b).verdict
+    }
+
+    #[test]
+    fn clear_differences_pick_a_side() {
+        assert_eq!(verdict(100, 10.0, 1.0), Verdict::BHigher);
+        assert_eq!(verdict(100, -10.0, 1.0), Verdict::AHigher);
+    }
+
+    #[test]
+    fn equal_results_are_no_difference() {
+        assert_eq!(verdict(100, 0.0, 0.0), Verdict::NoDifference);
+        assert_eq!(verdict(100, 0.0, 5.0), Verdict::NoDifference);
+    }
+
+    #[test]
+    fn small_or_noisy_samples_are_undecided() {
+        assert_eq!(verdict(10, 10.0, 1.0), Verdict::TooFewRuns(MIN_RUNS));
+        match verdict(100, 0.1, 10.0) {
+            Verdict::TooFewRuns(needed) => assert!(needed > 100),
+            verdict => panic!("expected too few runs, got {:?}", verdict),
+        }
+    }
+
+    #[test]
+    fn paired_runs_cancel_the_shared_noise() {
+        // Both sides see the same seed, so only the 1.0 offset is left in the difference
+        let comparisons = compare(&0.0, &1.0, 50, |offset, rng| Score(rng.gen_range(0.0..1000.0) + offset));
+        assert!(comparisons.iter().all(|c| c.verdict == Verdict::BHigher));
+        assert!((comparisons[0].difference.mean - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,207 @@
+use indoc::indoc;
+use rand::distributions::{Distribution, WeightedIndex};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use statistical::mean;
+use super::fighting::{fight, FightState, FightingSimConfig, Monster, RESPAWN_TIME};
+use super::batch::{per_hour, Metric, Metrics};
+use super::profit::{Items, Profit};
+use super::{format_duration_as_hms, min_max};
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MonsterSequence {
+    // Monsters are fought in order, the dungeon is completed after the last one
+    Dungeon(Vec<Monster>),
+    // Every next monster is picked at random, weighted by the first value
+    Area(Vec<(u32, Monster)>),
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct DungeonSimResult {
+    pub time: u32,
+    pub enemy_killed: u32,
+    pub dungeons_completed: u32,
+    pub completion_time: u32,  // sum of the completed dungeon runs durations
+    pub died: bool,
+    pub gold: u32,
+    pub xp: u32,
+    pub loot: Items,
+    pub consumed: Items,
+}
+
+impl Profit for DungeonSimResult {
+    fn time(&self) -> f64 {
+        self.time as f64
+    }
+
+    fn gold(&self) -> f64 {
+        self.gold as f64
+    }
+
+    fn loot(&self) -> &Items {
+        &self.loot
+    }
+
+    fn consumed(&self) -> &Items {
+        &self.consumed
+    }
+}
+
+impl Metrics for DungeonSimResult {
+    fn metric(&self, metric: Metric) -> f64 {
+        let time = self.time as f64;
+        match metric {
+            Metric::Time => time,
+            Metric::GoldPerHour => per_hour(self.gold as f64, time),
+            Metric::KillsPerHour => per_hour(self.enemy_killed as f64, time),
+            Metric::XpPerHour => per_hour(self.xp as f64, time),
+            Metric::Survival => if self.died { 0.0 } else { 1.0 },
+        }
+    }
+}
+
+
+// Fails on an empty dungeon or an area without any positive weight
+pub fn sim(config: &FightingSimConfig, sequence: &MonsterSequence) -> Result<DungeonSimResult, String> {
+    let mut rng = rand::thread_rng();
+
+    let mut state = FightState::new(config);
+    let mut enemy_killed = 0;
+    let mut dungeons_completed = 0;
+    let mut completion_time = dec!(0);
+    let mut dungeon_start_time = state.time;
+    let mut monster_index = 0;
+
+    let area_weights = match sequence {
+        MonsterSequence::Area(pool) => Some(
+            WeightedIndex::new(pool.iter().map(|(w, _)| *w)).map_err(|e| format!("Invalid monster area: {}", e))?
+        ),
+        MonsterSequence::Dungeon(monsters) if monsters.is_empty() => return Err("Dungeon has no monsters".to_string()),
+        MonsterSequence::Dungeon(_) => None,
+    };
+
+    let max_time = config.max_time();
+    while state.can_fight(config) && state.time < max_time {
+        let enemy = match sequence {
+            MonsterSequence::Dungeon(monsters) => &monsters[monster_index],
+            MonsterSequence::Area(pool) => &pool[area_weights.as_ref().unwrap().sample(&mut rng)].1,
+        };
+        if !fight(config, enemy, &mut state, max_time, &mut rng) {
+            continue;
+        }
+        state.time += RESPAWN_TIME;
+        enemy_killed += 1;
+
+        if let MonsterSequence::Dungeon(monsters) = sequence {
+            monster_index += 1;
+            if monster_index == monsters.len() {
+                dungeons_completed += 1;
+                completion_time += state.time - dungeon_start_time;
+                dungeon_start_time = state.time;
+                monster_index = 0;
+            }
+        }
+    }
+
+    Ok(DungeonSimResult {
+        time: state.time.to_u32().unwrap(),
+        enemy_killed,
+        dungeons_completed,
+        completion_time: completion_time.to_u32().unwrap(),
+        died: !state.is_alive(),
+        gold: state.gold,
+        xp: state.xp(config),
+        consumed: state.consumed(config),
+        loot: state.loot,
+    })
+}
+
+
+pub fn format_dungeon_results(results: &[DungeonSimResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    let mut time: Vec<f64> = Vec::new();
+    let mut enemy_killed: Vec<f64> = Vec::new();
+    let mut completed: Vec<f64> = Vec::new();
+    let mut completion_time_sum = 0.0;
+    let mut completed_sum = 0.0;
+    let mut deaths = 0.0;
+
+    for r in results {
+        time.push(r.time as f64);
+        enemy_killed.push(r.enemy_killed as f64);
+        completed.push(r.dungeons_completed as f64);
+        completion_time_sum += r.completion_time as f64;
+        completed_sum += r.dungeons_completed as f64;
+        if r.died {
+            deaths += 1.0;
+        }
+    }
+    let hours = time.iter().sum::<f64>() / 3600.0;
+    let (min_completed, max_completed) = min_max(&completed);
+    let mean_completion_time = if completed_sum > 0.0 { completion_time_sum / completed_sum } else { 0.0 };
+    // Every run ends either with a death or a timeout, so attempts are clears plus deaths
+    let death_risk = if completed_sum + deaths > 0.0 { deaths / (completed_sum + deaths) } else { 0.0 };
+
+    format!(
+        indoc!(r#"
+        Mean time: {}
+        Died: {:.2}%
+        ------------------------
+        Mean killed: {:.2}
+        Killed per hour: {:.2}
+        ------------------------
+        Mean dungeons completed: {:.2}
+        Min dungeons completed: {}
+        Max dungeons completed: {}
+        Dungeons per hour: {:.2}
+        Mean dungeon time: {}
+        Death risk per dungeon: {:.2}%
+        "#),
+        format_duration_as_hms(mean(&time)),
+        deaths / results.len() as f64 * 100.0,
+        mean(&enemy_killed),
+        enemy_killed.iter().sum::<f64>() / hours,
+        mean(&completed),
+        min_completed,
+        max_completed,
+        completed_sum / hours,
+        format_duration_as_hms(mean_completion_time),
+        death_risk * 100.0,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FightingSimConfig {
+        let mut config = FightingSimConfig::new(720, 8, 8, 1, 111, 0.76, 3.0, 300, 0, 116, 0.35, 2.4);
+        config.max_time = Some(dec!(600));
+        config
+    }
+
+    #[test]
+    fn rejects_empty_sequences() {
+        assert!(sim(&config(), &MonsterSequence::Dungeon(Vec::new())).is_err());
+        assert!(sim(&config(), &MonsterSequence::Area(Vec::new())).is_err());
+        let zero_weights = MonsterSequence::Area(vec![(0, Monster::new(100, 0, 10, 0.3, 3.0))]);
+        assert!(sim(&config(), &zero_weights).is_err());
+    }
+
+    #[test]
+    fn runs_a_valid_dungeon() {
+        let dungeon = MonsterSequence::Dungeon(vec![Monster::new(100, 0, 10, 0.3, 3.0)]);
+        let result = sim(&config(), &dungeon).unwrap();
+        assert!(result.enemy_killed > 0);
+        assert_eq!(result.dungeons_completed, result.enemy_killed);
+        assert!(result.xp > 0);
+        assert!(result.metric(Metric::KillsPerHour) > 0.0);
+        assert!(result.metric(Metric::XpPerHour) > 0.0);
+    }
+}
@@ -0,0 +1,119 @@
+use super::trace::Trace;
+
+pub const DEFAULT_INTERVAL: f64 = 300.0;  // 5 minutes
+
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Checkpoint {
+    pub time: f64,
+    pub mean: f64,
+    pub p5: f64,
+    pub p50: f64,
+    pub p95: f64,
+}
+
+
+// Health of every run at fixed time checkpoints. A dead player counts as zero
+// health for the rest of the session.
+#[derive(Debug, Clone)]
+pub struct HealthEnvelope {
+    start_health: i32,
+    times: Vec<f64>,
+    samples: Vec<Vec<f64>>,  // one per checkpoint
+}
+
+impl HealthEnvelope {
+    pub fn new(start_health: i32, max_time: f64, interval: f64) -> Self {
+        let count = (max_time / interval).floor() as usize + 1;
+        Self {
+            start_health,
+            times: (0..count).map(|i| i as f64 * interval).collect(),
+            samples: vec![Vec::new(); count],
+        }
+    }
+
+    pub fn add(&mut self, trace: &Trace) {
+        let mut events = trace.events.iter().peekable();
+        let mut health = self.start_health;
+        for (time, samples) in self.times.iter().zip(self.samples.iter_mut()) {
+            while let Some(event) = events.next_if(|e| e.time <= *time) {
+                health = event.health;
+            }
+            samples.push(health.max(0) as f64);
+        }
+    }
+
+    pub fn checkpoints(&self) -> Vec<Checkpoint> {
+        self.times.iter().zip(&self.samples)
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(&time, samples)| {
+                let mut sorted = samples.clone();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+                Checkpoint {
+                    time,
+                    mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+                    p5: percentile(0.05),
+                    p50: percentile(0.5),
+                    p95: percentile(0.95),
+                }
+            })
+            .collect()
+    }
+}
+
+
+pub fn format_envelope_csv(checkpoints: &[Checkpoint]) -> String {
+    let mut output = "time,mean,p5,p50,p95\n".to_string();
+    for c in checkpoints {
+        output += &format!("{},{:.2},{},{},{}\n", c.time, c.mean, c.p5, c.p50, c.p95);
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::super::trace::{Event, EventKind};
+    use super::*;
+
+    fn trace(events: &[(f64, i32)]) -> Trace {
+        let mut trace = Trace::recording();
+        trace.events = events.iter()
+            .map(|&(time, health)| Event { time, health, kind: EventKind::DamageTaken { damage: 0 } })
+            .collect();
+        trace
+    }
+
+    #[test]
+    fn samples_the_health_at_every_checkpoint() {
+        let mut envelope = HealthEnvelope::new(100, 30.0, 10.0);
+        envelope.add(&trace(&[(5.0, 80), (15.0, 60)]));
+        envelope.add(&trace(&[(10.0, 50), (25.0, -20)]));
+        let checkpoints = envelope.checkpoints();
+        let times: Vec<f64> = checkpoints.iter().map(|c| c.time).collect();
+        assert_eq!(times, vec![0.0, 10.0, 20.0, 30.0]);
+        assert_eq!(checkpoints[0].mean, 100.0);
+        // Events at the checkpoint time are already applied
+        assert_eq!((checkpoints[1].p5, checkpoints[1].p95), (50.0, 80.0));
+        // Dead runs count as zero for the rest of the session
+        assert_eq!((checkpoints[3].p5, checkpoints[3].p95), (0.0, 60.0));
+        assert_eq!(checkpoints[3].mean, 30.0);
+    }
+
+    #[test]
+    fn percentiles_come_from_the_sorted_runs() {
+        let mut envelope = HealthEnvelope::new(100, 10.0, 10.0);
+        for health in 1..=101 {
+            envelope.add(&trace(&[(1.0, health)]));
+        }
+        let checkpoint = envelope.checkpoints()[1];
+        assert_eq!((checkpoint.p5, checkpoint.p50, checkpoint.p95), (6.0, 51.0, 96.0));
+        assert_eq!(checkpoint.mean, 51.0);
+    }
+
+    #[test]
+    fn empty_envelopes_have_no_checkpoints() {
+        assert!(HealthEnvelope::new(100, 30.0, 10.0).checkpoints().is_empty());
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::path::Path;
+use std::str::FromStr;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use super::fighting::FightingSimConfig;
+use super::thieving::ThievingSimConfig;
+
+pub const DEFAULT_GEAR_FILE: &str = "gear.json";
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Slot {
+    Helmet,
+    Body,
+    Legs,
+    Boots,
+    Gloves,
+    Cape,
+    Amulet,
+    Ring,
+    Weapon,
+    Shield,
+    Quiver,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Skill {
+    Thieving,
+    Fighting,
+}
+
+impl FromStr for Skill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "thieving" => Ok(Skill::Thieving),
+            "fighting" => Ok(Skill::Fighting),
+            _ => Err(format!("Unknown skill: {}, expected thieving or fighting", s)),
+        }
+    }
+}
+
+
+// Bonuses added on top of the base config
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemStats {
+    pub max_health: i32,
+    pub regen: i32,  // health per regeneration tick
+    pub damage_min: i32,
+    pub damage_max: i32,
+    pub hit_chance: f32,
+    pub attack_speed: f32,  // seconds taken off the attack interval
+    pub damage_bonus: f32,  // 0.1 is +10% damage
+    pub lifesteal: f32,
+    pub reflect: f32,
+    pub steal_chance: f32,
+    pub steal_speed: f32,  // seconds taken off the steal interval
+    pub gold_bonus: f32,  // 0.1 is +10% gold
+}
+
+impl AddAssign for ItemStats {
+    fn add_assign(&mut self, other: Self) {
+        self.max_health += other.max_health;
+        self.regen += other.regen;
+        self.damage_min += other.damage_min;
+        self.damage_max += other.damage_max;
+        self.hit_chance += other.hit_chance;
+        self.attack_speed += other.attack_speed;
+        self.damage_bonus += other.damage_bonus;
+        self.lifesteal += other.lifesteal;
+        self.reflect += other.reflect;
+        self.steal_chance += other.steal_chance;
+        self.steal_speed += other.steal_speed;
+        self.gold_bonus += other.gold_bonus;
+    }
+}
+
+fn add_u16(value: u16, bonus: i32) -> u16 {
+    (value as i32 + bonus).clamp(0, u16::MAX as i32) as u16
+}
+
+fn sub_seconds(interval: Decimal, seconds: f32) -> Decimal {
+    // Thieving steps time by 0.1 second, so intervals are kept to one decimal place
+    (interval - Decimal::try_from(seconds).unwrap_or_default()).round_dp(1).max(dec!(0.1))
+}
+
+impl ItemStats {
+    // Only the stats the skill uses, the others do not change its results
+    fn values(&self, skill: Skill) -> Vec<f32> {
+        match skill {
+            Skill::Thieving => vec![
+                self.max_health as f32,
+                self.regen as f32,
+                self.steal_chance,
+                self.steal_speed,
+                self.gold_bonus,
+            ],
+            Skill::Fighting => vec![
+                self.max_health as f32,
+                self.regen as f32,
+                self.damage_min as f32,
+                self.damage_max as f32,
+                self.hit_chance,
+                self.attack_speed,
+                self.damage_bonus,
+                self.lifesteal,
+                self.reflect,
+            ],
+        }
+    }
+
+    // True if every stat of the skill is at least as good and at least one is better
+    pub fn dominates(&self, other: &ItemStats, skill: Skill) -> bool {
+        let (a, b) = (self.values(skill), other.values(skill));
+        a.iter().zip(&b).all(|(x, y)| x >= y) && a.iter().zip(&b).any(|(x, y)| x > y)
+    }
+
+    pub fn apply_thieving(&self, base: &ThievingSimConfig) -> ThievingSimConfig {
+        let mut config = base.clone();
+        config.max_health += self.max_health;
+        config.health_regeneration_amount += self.regen;
+        config.steal_success_chance = (config.steal_success_chance + self.steal_chance).min(1.0);
+        config.steal_interval = sub_seconds(config.steal_interval, self.steal_speed);
+        config.min_gold = (config.min_gold as f32 * (1.0 + self.gold_bonus)) as i32;
+        config.max_gold = (config.max_gold as f32 * (1.0 + self.gold_bonus)) as i32;
+        config
+    }
+
+    pub fn apply_fighting(&self, base: &FightingSimConfig) -> FightingSimConfig {
+        let mut config = base.clone();
+        config.player_health = add_u16(config.player_health, self.max_health);
+        config.player_health_regen = add_u16(config.player_health_regen, self.regen);
+        config.player_damage_min = add_u16(config.player_damage_min, self.damage_min);
+        config.player_damage_max = add_u16(config.player_damage_max, self.damage_max).max(config.player_damage_min);
+        config.player_hit_chance = (config.player_hit_chance + self.hit_chance).min(1.0);
+        config.player_attack_interval = sub_seconds(config.player_attack_interval, self.attack_speed);
+        config.weapon.damage_bonus += self.damage_bonus;
+        config.weapon.lifesteal += self.lifesteal;
+        config.weapon.reflect += self.reflect;
+        config
+    }
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub name: String,
+    pub slot: Slot,
+    pub stats: ItemStats,
+}
+
+
+// Extra stats granted while enough items of the set are equipped
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SetBonus {
+    pub name: String,
+    pub items: Vec<String>,
+    pub pieces: usize,
+    pub stats: ItemStats,
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Loadout {
+    pub name: String,
+    pub items: BTreeMap<Slot, Item>,
+}
+
+impl Loadout {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            items: BTreeMap::new(),
+        }
+    }
+
+    // Puts the item in its slot, returning the item it replaced
+    pub fn equip(&mut self, item: Item) -> Option<Item> {
+        self.items.insert(item.slot, item)
+    }
+
+    pub fn stats(&self, sets: &[SetBonus]) -> ItemStats {
+        let mut stats = ItemStats::default();
+        for item in self.items.values() {
+            stats += item.stats;
+        }
+        for set in sets {
+            let equipped = self.items.values().filter(|item| set.items.contains(&item.name)).count();
+            if equipped >= set.pieces {
+                stats += set.stats;
+            }
+        }
+        stats
+    }
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Equipment {
+    pub sets: Vec<SetBonus>,
+    pub loadouts: Vec<Loadout>,
+    pub inventory: Vec<Item>,  // owned items, searched by the optimizer
+}
+
+impl Equipment {
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let json = std::fs::read(path)?;
+        let equipment: Equipment = serde_json::from_slice(&json)?;
+        Ok(equipment)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        let json = serde_json::to_string_pretty(&self)?;
+        std::fs::write(path, json)
+    }
+
+    pub fn loadout(&self, name: &str) -> Option<&Loadout> {
+        self.loadouts.iter().find(|l| l.name == name)
+    }
+
+    pub fn stats(&self, name: &str) -> Option<ItemStats> {
+        self.loadout(name).map(|l| l.stats(&self.sets))
+    }
+}
@@ -4,32 +4,242 @@ use rand::Rng;
 use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use super::format_duration_as_hms;
+use serde::{Deserialize, Serialize};
+use super::food::{Food, FoodState};
+use super::potion::{Potion, PotionBonus, PotionState};
+use super::prayer::{PrayerBonus, PrayerConfig, PrayerState};
+use super::profit::{add_item, ItemDrop, Items, Profit};
+use super::supplies::{AttackStyle, Supply, SupplyState};
+use super::session::{Chained, EndState};
+use super::summoning::{FamiliarBonus, Summoned, Summoning, SummoningResult, SummoningState};
+use super::trace::{EventKind, Trace};
+use super::batch::{per_hour, Metric, Metrics};
+use super::{format_duration_as_hms, min_max};
 use statistical::{mean, median};
 
-const RESPAWN_TIME: Decimal = dec!(3);
+pub const RESPAWN_TIME: Decimal = dec!(3);
+pub const MAX_TIME: Decimal = dec!(28800);  // 8 hours
+pub const XP_PER_DAMAGE: f32 = 4.0;
 
 
 pub struct FightingSimResult {
-    time: u16,
-    enemy_killed: u16
+    time: u32,
+    enemy_killed: u16,
+    damage: DamageBreakdown,
+    prayer_points_used: u32,
+    prayer_potions_used: u32,
+    prayer_ran_out_at: Option<u32>,
+    potions_used: u32,
+    supply_cost: f64,
+    supplies_ran_out_at: Option<u32>,
+    gold: u32,
+    xp: u32,
+    died: bool,
+    loot: Items,
+    consumed: Items,
+    end: EndState,
+    summoning: SummoningResult,
 }
 
+impl Profit for FightingSimResult {
+    fn time(&self) -> f64 {
+        self.time as f64
+    }
+
+    fn gold(&self) -> f64 {
+        self.gold as f64
+    }
+
+    fn loot(&self) -> &Items {
+        &self.loot
+    }
+
+    fn consumed(&self) -> &Items {
+        &self.consumed
+    }
+}
+
+impl Summoned for FightingSimResult {
+    fn summoning(&self) -> &SummoningResult {
+        &self.summoning
+    }
+}
+
+impl Chained for FightingSimResult {
+    fn end_state(&self) -> EndState {
+        self.end
+    }
+
+    fn xp(&self) -> f64 {
+        self.xp as f64
+    }
+
+    fn died(&self) -> bool {
+        self.died
+    }
+}
+
+impl Metrics for FightingSimResult {
+    fn metric(&self, metric: Metric) -> f64 {
+        let time = self.time as f64;
+        match metric {
+            Metric::Time => time,
+            Metric::GoldPerHour => per_hour(self.gold as f64, time),
+            Metric::KillsPerHour => per_hour(self.enemy_killed as f64, time),
+            Metric::XpPerHour => per_hour(self.xp as f64, time),
+            Metric::Survival => if self.died { 0.0 } else { 1.0 },
+        }
+    }
+}
+
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DamageBreakdown {
+    pub normal: u32,
+    pub special: u32,
+    pub reflect: u32,
+    pub lifesteal: u32,  // health restored, not damage
+}
+
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DotKind {
+    #[default]
+    Burn,  // does not stack, a new burn replaces the active one
+    Poison,  // stacks, every application ticks on its own
+    Bleed,  // stacks and stops health regeneration while active
+}
+
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct DamageOverTime {
+    pub kind: DotKind,
+    pub damage: u16,  // per tick
+    pub ticks: u16,
+    pub interval: Decimal,  // in seconds
+}
+
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpecialAttack {
+    pub chance: f32,  // chance to be used instead of a normal attack
+    pub hits: u16,
+    pub damage_min: u16,  // per hit
+    pub damage_max: u16,
+    pub stun: Decimal,  // in seconds, applied if any hit lands
+    pub dot: Option<DamageOverTime>,  // applied if any hit lands
+    pub heal: u16,  // enemy heals itself on use
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Monster {
+    pub health: u16,
+    pub damage_min: u16,
+    pub damage_max: u16,
+    pub hit_chance: f32,
+    pub attack_interval: Decimal,
+    #[serde(default)]
+    pub specials: Vec<SpecialAttack>,
+    #[serde(default)]
+    pub gold_min: u32,
+    #[serde(default)]
+    pub gold_max: u32,
+    #[serde(default)]
+    pub drops: Vec<ItemDrop>,
+}
+
+impl Monster {
+    pub fn new(health: u16, damage_min: u16, damage_max: u16, hit_chance: f32, attack_interval: f32) -> Self {
+        Self {
+            health,
+            damage_min,
+            damage_max,
+            hit_chance,
+            attack_interval: Decimal::try_from(attack_interval).unwrap(),
+            specials: Vec::new(),
+            gold_min: 0,
+            gold_max: 0,
+            drops: Vec::new(),
+        }
+    }
+
+    fn roll_special<R: Rng>(&self, rng: &mut R) -> Option<&SpecialAttack> {
+        if self.specials.is_empty() {
+            return None;
+        }
+        let mut roll = rng.gen::<f32>();
+        for special in &self.specials {
+            if roll < special.chance {
+                return Some(special);
+            }
+            roll -= special.chance;
+        }
+        None
+    }
+}
 
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSpecialAttack {
+    pub chance: f32,  // rolled when there is enough energy
+    pub energy_cost: u16,
+    pub hits: u16,
+    pub damage_bonus: f32,  // on top of the weapon bonus, 0.5 is +50%
+    pub always_hits: bool,
+}
+
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeaponEffects {
+    pub special: Option<PlayerSpecialAttack>,
+    pub max_energy: u16,
+    pub energy_per_attack: u16,
+    pub damage_bonus: f32,  // 0.1 is +10% damage
+    pub always_hits: bool,
+    pub lifesteal: f32,  // part of the damage dealt restored as health
+    pub reflect: f32,  // part of the damage taken dealt back to the enemy
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct FightingSimConfig {
-    player_health: u16,
-    player_health_regen: u16,
-    player_regen_interval: Decimal,
-    player_damage_min: u16,
-    player_damage_max: u16,
-    player_hit_chance: f32,
-    player_attack_interval: Decimal,
+    pub player_health: u16,
+    pub player_health_regen: u16,
+    pub player_regen_interval: Decimal,
+    pub player_damage_min: u16,
+    pub player_damage_max: u16,
+    pub player_hit_chance: f32,
+    pub player_attack_interval: Decimal,
+    #[serde(default)]
+    pub weapon: WeaponEffects,
+    #[serde(default)]
+    pub prayer: PrayerConfig,
+    #[serde(default)]
+    pub potion: Option<Potion>,
+    #[serde(default)]
+    pub supplies: Vec<Supply>,
+    #[serde(default)]
+    pub fallback_style: Option<AttackStyle>,  // used when out of supplies, otherwise the fighting stops
+    #[serde(default)]
+    pub food: Option<Food>,
+    #[serde(default = "default_xp_per_damage")]
+    pub xp_per_damage: f32,
+    #[serde(default)]
+    pub start_health: Option<u16>,  // player health if not set
+    #[serde(default)]
+    pub max_time: Option<Decimal>,  // MAX_TIME if not set
+    #[serde(default)]
+    pub summoning: Summoning,
+
+    pub enemy: Monster,
+}
 
-    enemy_health: u16,
-    enemy_damage_min: u16,
-    enemy_damage_max: u16,
-    enemy_hit_chance: f32,
-    enemy_attack_interval: Decimal
+fn default_xp_per_damage() -> f32 {
+    XP_PER_DAMAGE
 }
 
 impl FightingSimConfig {
@@ -55,93 +265,489 @@ impl FightingSimConfig {
             player_damage_max,
             player_hit_chance,
             player_attack_interval: Decimal::try_from(player_attack_interval).unwrap(),
+            weapon: WeaponEffects::default(),
+            prayer: PrayerConfig::default(),
+            potion: None,
+            supplies: Vec::new(),
+            fallback_style: None,
+            food: None,
+            xp_per_damage: XP_PER_DAMAGE,
+            start_health: None,
+            max_time: None,
+            summoning: Summoning::default(),
+
+            enemy: Monster::new(
+                enemy_health,
+                enemy_damage_min,
+                enemy_damage_max,
+                enemy_hit_chance,
+                enemy_attack_interval,
+            ),
+        }
+    }
 
-            enemy_health,
-            enemy_damage_min,
-            enemy_damage_max,
-            enemy_hit_chance,
-            enemy_attack_interval: Decimal::try_from(enemy_attack_interval).unwrap(),
+    pub fn attack_style(&self) -> AttackStyle {
+        AttackStyle {
+            damage_min: self.player_damage_min,
+            damage_max: self.player_damage_max,
+            hit_chance: self.player_hit_chance,
+            attack_interval: self.player_attack_interval,
         }
     }
+    pub fn max_time(&self) -> Decimal {
+        self.max_time.unwrap_or(MAX_TIME)
+    }
 }
 
 
-// Simulation function
-pub fn sim(config: &FightingSimConfig) -> FightingSimResult {
-    let mut rng = rand::thread_rng();
+struct ActiveDot {
+    kind: DotKind,
+    damage: u16,
+    ticks_left: u16,
+    interval: Decimal,
+    timer: Decimal,
+}
 
-    let mut player_current_health = config.player_health;
-    let mut time = dec!(0);
-    let mut enemy_killed = 0;
-    let mut regen_timer = dec!(0);
-    let mut enemy_current_health = config.enemy_health;
-    let max_time = Decimal::from(60 * 60 * 8);
-
-    while player_current_health > 0 && time < max_time {
-        // Player attacks
-        let attack_roll = rng.gen::<f32>();
-        if attack_roll <= config.player_hit_chance {
-            let damage = rng.gen_range(
-                config.player_damage_min..config.player_damage_max + 1
+
+// Player state that carries over from one fight to the next
+pub struct FightState {
+    pub player_health: u16,
+    pub time: Decimal,
+    pub damage: DamageBreakdown,
+    pub prayer: PrayerState,
+    pub potion: PotionState,
+    pub supplies: SupplyState,
+    pub food: FoodState,
+    pub summoning: SummoningState,
+    pub gold: u32,
+    pub loot: Items,
+    pub trace: Trace,
+    regen_timer: Decimal,
+    stun_time: Decimal,
+    dots: Vec<ActiveDot>,
+    energy: u16,
+}
+
+impl FightState {
+    pub fn new(config: &FightingSimConfig) -> Self {
+        Self {
+            player_health: config.start_health.map_or(config.player_health, |h| min(h, config.player_health)),
+            time: dec!(0),
+            damage: DamageBreakdown::default(),
+            prayer: PrayerState::new(&config.prayer),
+            potion: PotionState::new(config.potion.as_ref()),
+            supplies: SupplyState::new(&config.supplies),
+            food: FoodState::new(config.food.as_ref()),
+            summoning: SummoningState::new(&config.summoning),
+            gold: 0,
+            loot: Items::new(),
+            trace: Trace::default(),
+            regen_timer: dec!(0),
+            stun_time: dec!(0),
+            dots: Vec::new(),
+            energy: config.weapon.max_energy,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.player_health > 0
+    }
+
+    // False once the player is dead or out of supplies with no style to fall back to
+    pub fn can_fight(&self, config: &FightingSimConfig) -> bool {
+        self.is_alive() && (self.supplies.ran_out_at.is_none() || config.fallback_style.is_some())
+    }
+
+    pub fn xp(&self, config: &FightingSimConfig) -> u32 {
+        let damage = self.damage.normal + self.damage.special + self.damage.reflect;
+        (damage as f32 * config.xp_per_damage) as u32
+    }
+
+    // Potions, food, prayer potions and supplies used so far
+    pub fn consumed(&self, config: &FightingSimConfig) -> Items {
+        let mut consumed = Items::new();
+        if let Some(potion) = &config.potion {
+            add_item(&mut consumed, &potion.name, self.potion.used);
+        }
+        if let Some(food) = &config.food {
+            add_item(&mut consumed, &food.name, self.food.eaten);
+        }
+        add_item(&mut consumed, &config.prayer.potion_name, self.prayer.potions_used);
+        for (supply, used) in config.supplies.iter().zip(&self.supplies.used) {
+            add_item(&mut consumed, &supply.name, *used);
+        }
+        for (tablet, used) in self.summoning.result(&config.summoning).tablets_used {
+            add_item(&mut consumed, &tablet, used);
+        }
+        consumed
+    }
+
+    fn loot_enemy<R: Rng>(&mut self, enemy: &Monster, gold_bonus: f32, rng: &mut R) {
+        let gold = if enemy.gold_max > 0 { rng.gen_range(enemy.gold_min..=enemy.gold_max) } else { 0 };
+        let extra_gold = (gold as f32 * gold_bonus) as u32;
+        self.summoning.contribution.gold += extra_gold as f64;
+        let gold = gold + extra_gold;
+        self.gold += gold;
+        let dropped = ItemDrop::roll(&enemy.drops, &mut self.loot, rng);
+        self.record(EventKind::Kill { gold });
+        for (item, amount) in dropped {
+            self.record(EventKind::Loot { item, amount });
+        }
+    }
+
+    fn eat(&mut self, config: &FightingSimConfig) {
+        let healed = self.food.eat(config.food.as_ref(), self.player_health as u32, config.player_health as u32);
+        self.player_health += healed as u16;
+        if healed > 0 {
+            self.record(EventKind::Eat { heal: healed });
+        }
+    }
+
+    fn record(&mut self, kind: EventKind) {
+        self.trace.record(self.time, self.player_health as i32, kind);
+    }
+
+    fn attack_style(&self, config: &FightingSimConfig) -> AttackStyle {
+        match config.fallback_style {
+            Some(style) if self.supplies.ran_out_at.is_some() => style,
+            _ => config.attack_style(),
+        }
+    }
+
+    fn take_damage(&mut self, damage: u16) {
+        self.player_health = self.player_health.saturating_sub(damage);
+    }
+
+    fn apply_dot(&mut self, dot: DamageOverTime) {
+        if dot.kind == DotKind::Burn {
+            self.dots.retain(|d| d.kind != DotKind::Burn);
+        }
+        self.dots.push(ActiveDot {
+            kind: dot.kind,
+            damage: dot.damage,
+            ticks_left: dot.ticks,
+            interval: dot.interval,
+            timer: dec!(0),
+        });
+    }
+
+    fn tick_dots(&mut self, elapsed: Decimal) {
+        let mut damage: u16 = 0;
+        for dot in self.dots.iter_mut() {
+            dot.timer += elapsed;
+            while dot.ticks_left > 0 && dot.timer >= dot.interval {
+                damage = damage.saturating_add(dot.damage);
+                dot.ticks_left -= 1;
+                dot.timer -= dot.interval;
+            }
+        }
+        self.dots.retain(|dot| dot.ticks_left > 0);
+        self.take_damage(damage);
+        if damage > 0 {
+            self.record(EventKind::DamageTaken { damage: damage as u32 });
+        }
+    }
+}
+
+
+// Returns the damage taken by the player, and the damage before any reduction
+fn enemy_attack<R: Rng>(
+    enemy: &Monster,
+    enemy_current_health: &mut u16,
+    defence_bonus: f32,
+    damage_reduction: f32,
+    state: &mut FightState,
+    rng: &mut R,
+) -> (u16, u32) {
+    let reduce = |damage: u16| (damage as f32 * (1.0 - damage_reduction)) as u16;
+    let hit_chance = enemy.hit_chance * (1.0 - defence_bonus);
+    let special = match enemy.roll_special(rng) {
+        Some(special) => special,
+        None => {
+            let enemy_attack_roll = rng.gen::<f32>();
+            if enemy_attack_roll <= hit_chance {
+                let raw_damage = rng.gen_range(enemy.damage_min..enemy.damage_max + 1);
+                let enemy_damage = reduce(raw_damage);
+                state.take_damage(enemy_damage);
+                return (enemy_damage, raw_damage as u32);
+            }
+            return (0, 0);
+        }
+    };
+
+    let mut damage_taken: u16 = 0;
+    let mut raw_damage: u32 = 0;
+    let mut landed = false;
+    for _ in 0..special.hits {
+        if rng.gen::<f32>() <= hit_chance {
+            let raw = rng.gen_range(special.damage_min..special.damage_max + 1);
+            raw_damage += raw as u32;
+            let damage = reduce(raw);
+            state.take_damage(damage);
+            damage_taken = damage_taken.saturating_add(damage);
+            landed = true;
+        }
+    }
+    if landed {
+        state.stun_time = state.stun_time.max(special.stun);
+        if let Some(dot) = special.dot {
+            state.apply_dot(dot);
+        }
+    }
+    *enemy_current_health = min(enemy_current_health.saturating_add(special.heal), enemy.health);
+    (damage_taken, raw_damage)
+}
+
+
+// Returns the rolled damage, whether the weapon special attack was used and the
+// part of the damage the familiars added
+fn player_attack<R: Rng>(
+    config: &FightingSimConfig,
+    style: &AttackStyle,
+    bonus: &PrayerBonus,
+    potion_bonus: &PotionBonus,
+    familiar: &FamiliarBonus,
+    state: &mut FightState,
+    rng: &mut R,
+) -> (u16, bool, f32) {
+    let weapon = &config.weapon;
+    let base_hit_chance = style.hit_chance + bonus.accuracy + potion_bonus.success_chance;
+    let hit_chance = base_hit_chance + familiar.success_chance;
+    let damage_bonus = weapon.damage_bonus + bonus.damage + potion_bonus.damage + familiar.damage;
+    let special = weapon.special.filter(|s| state.energy >= s.energy_cost && rng.gen::<f32>() < s.chance);
+    let (hits, damage_bonus, always_hits) = match special {
+        Some(s) => {
+            state.energy -= s.energy_cost;
+            (s.hits, damage_bonus + s.damage_bonus, weapon.always_hits || s.always_hits)
+        }
+        None => (1, damage_bonus, weapon.always_hits),
+    };
+    state.energy = min(state.energy.saturating_add(weapon.energy_per_attack), weapon.max_energy);
+
+    let mut damage: u16 = 0;
+    let mut familiar_damage = 0.0;
+    for _ in 0..hits {
+        let attack_roll = if always_hits { 0.0 } else { rng.gen::<f32>() };
+        if attack_roll <= hit_chance {
+            if attack_roll > base_hit_chance {
+                state.summoning.contribution.successes += 1;
+            }
+            let hit = rng.gen_range(
+                style.damage_min..style.damage_max + 1
             );
-            enemy_current_health = if damage > enemy_current_health { 0 } else { enemy_current_health - damage };
+            damage = damage.saturating_add((hit as f32 * (1.0 + damage_bonus)) as u16);
+            familiar_damage += hit as f32 * familiar.damage;
+        }
+    }
+    (damage, special.is_some(), familiar_damage)
+}
+
+
+// Fights a single enemy until it dies, the player dies or max_time is reached.
+// Returns true if the enemy was killed.
+pub fn fight<R: Rng>(
+    config: &FightingSimConfig,
+    enemy: &Monster,
+    state: &mut FightState,
+    max_time: Decimal,
+    rng: &mut R,
+) -> bool {
+    let mut enemy_current_health = enemy.health;
+
+    while state.can_fight(config) && state.time < max_time {
+        // Prayer points are drained once per player attack interval
+        let bonus = state.prayer.pray(&config.prayer, state.time);
+        let potion_bonus = state.potion.use_charge(config.potion.as_ref());
+        let familiar = state.summoning.bonus(&config.summoning);
+
+        if state.stun_time > dec!(0) {
+            // Stunned player skips the attack
+            state.stun_time = (state.stun_time - state.attack_style(config).attack_interval).max(dec!(0));
+        } else if !state.supplies.consume(&config.supplies, state.time, rng) && config.fallback_style.is_none() {
+            // Out of ammunition or runes
+            return false;
+        } else {
+            // Player attacks, only attacks use the familiar charges
+            state.summoning.use_charges(&config.summoning);
+            let style = state.attack_style(config);
+            let (damage, is_special, familiar_damage) = player_attack(config, &style, &bonus, &potion_bonus, &familiar, state, rng);
+            let dealt = min(damage, enemy_current_health);
+            // Overkill damage is not credited to the familiars either
+            if damage > 0 {
+                state.summoning.contribution.damage += (familiar_damage * dealt as f32 / damage as f32) as f64;
+            }
+            if dealt > 0 {
+                state.record(EventKind::Hit { damage: dealt as u32, special: is_special });
+            } else {
+                state.record(EventKind::Miss);
+            }
+            if is_special {
+                state.damage.special += dealt as u32;
+            } else {
+                state.damage.normal += dealt as u32;
+            }
+            let heal = (dealt as f32 * config.weapon.lifesteal) as u16;
+            state.damage.lifesteal += min(heal, config.player_health.saturating_sub(state.player_health)) as u32;
+            state.player_health = min(state.player_health.saturating_add(heal), config.player_health);
+
+            enemy_current_health -= dealt;
             if enemy_current_health == 0 {
-                enemy_current_health = config.enemy_health;
-                time += RESPAWN_TIME;  // Waiting for next enemy to respawn
-                enemy_killed += 1;
-                continue;
+                state.loot_enemy(enemy, familiar.gold, rng);
+                return true;
             }
         }
         // Enemy attacks
-        let enemy_attack_roll = rng.gen::<f32>();
-        if enemy_attack_roll <= config.enemy_hit_chance {
-            let enemy_damage= rng.gen_range(
-                config.enemy_damage_min..config.enemy_damage_max + 1
-            );
-            player_current_health = if enemy_damage > player_current_health { 0 } else { player_current_health - enemy_damage };
+        let stun_before = state.stun_time;
+        let (damage_taken, raw_damage) = enemy_attack(
+            enemy,
+            &mut enemy_current_health,
+            bonus.defence,
+            potion_bonus.damage_reduction + familiar.damage_reduction,
+            state,
+            rng,
+        );
+        state.summoning.contribution.prevent_damage(raw_damage as f64, potion_bonus.damage_reduction, familiar.damage_reduction);
+        if damage_taken > 0 {
+            state.record(EventKind::DamageTaken { damage: damage_taken as u32 });
         }
+        if state.stun_time > stun_before {
+            let seconds = state.stun_time.to_f64().unwrap_or_default();
+            state.record(EventKind::Stun { seconds });
+        }
+        let reflected = min((damage_taken as f32 * config.weapon.reflect) as u16, enemy_current_health);
+        if reflected > 0 {
+            state.damage.reflect += reflected as u32;
+            enemy_current_health -= reflected;
+            if enemy_current_health == 0 {
+                state.loot_enemy(enemy, familiar.gold, rng);
+                return true;
+            }
+        }
+
+        let attack_interval = state.attack_style(config).attack_interval;
+
+        // Burn, poison and bleed damage
+        state.tick_dots(attack_interval);
+        state.eat(config);
 
         // Health regeneration for the player
-        regen_timer = regen_timer + config.player_attack_interval;
-        while player_current_health > 0 && regen_timer >= config.player_regen_interval {
-            player_current_health += config.player_health_regen;
-            regen_timer -= config.player_regen_interval;
+        let health_before_regen = state.player_health;
+        state.regen_timer = state.regen_timer + attack_interval;
+        let health_regen = (config.player_health_regen as f32 * (1.0 + bonus.heal + potion_bonus.regen + familiar.regen)) as u16;
+        let bleeding = state.dots.iter().any(|d| d.kind == DotKind::Bleed);
+        while state.player_health > 0 && state.regen_timer >= config.player_regen_interval {
+            if !bleeding {
+                let regenerated = min(state.player_health.saturating_add(health_regen), config.player_health) - state.player_health;
+                state.player_health += regenerated;
+                state.summoning.contribution.healing += (config.player_health_regen as f32 * familiar.regen).min(regenerated as f32) as f64;
+            }
+            state.regen_timer -= config.player_regen_interval;
         }
         // Update time
-        time += config.player_attack_interval;
+        state.time += attack_interval;
 
         // Health cap
-        player_current_health = min(player_current_health, config.player_health);
+        state.player_health = min(state.player_health, config.player_health);
+        if state.player_health > health_before_regen {
+            let amount = (state.player_health - health_before_regen) as u32;
+            state.record(EventKind::Regen { amount });
+        }
     }
-    return FightingSimResult{time: time.to_u16().unwrap(), enemy_killed}
+    false
 }
 
-fn min_max<T>(values: &[T]) -> (T, T)
-where
-    T: Copy + PartialOrd,
-{
-    let mut min = values[0];
-    let mut max = values[0];
-    for &v in values.iter() {
-        if v.partial_cmp(&min).unwrap() == std::cmp::Ordering::Less {
-            min = v;
-        }
-        if v.partial_cmp(&max).unwrap() == std::cmp::Ordering::Greater {
-            max = v;
+
+// Simulation function
+pub fn sim(config: &FightingSimConfig) -> FightingSimResult {
+    sim_with_rng(config, &mut rand::thread_rng())
+}
+
+// Same as `sim`, with the random source passed in so runs can be seeded
+pub fn sim_with_rng<R: Rng>(config: &FightingSimConfig, rng: &mut R) -> FightingSimResult {
+    simulate(config, FightState::new(config), rng).0
+}
+
+// Same as `sim_with_rng`, also returning every event of the run
+pub fn sim_traced<R: Rng>(config: &FightingSimConfig, rng: &mut R) -> (FightingSimResult, Trace) {
+    let state = FightState {
+        trace: Trace::recording(),
+        ..FightState::new(config)
+    };
+    simulate(config, state, rng)
+}
+
+fn simulate<R: Rng>(config: &FightingSimConfig, mut state: FightState, rng: &mut R) -> (FightingSimResult, Trace) {
+    let mut enemy_killed = 0;
+
+    let max_time = config.max_time();
+    while state.can_fight(config) && state.time < max_time {
+        if fight(config, &config.enemy, &mut state, max_time, rng) {
+            state.time += RESPAWN_TIME;  // Waiting for next enemy to respawn
+            enemy_killed += 1;
         }
     }
-    (min, max)
+    if !state.is_alive() {
+        state.record(EventKind::Death);
+    }
+    let result = FightingSimResult{
+        time: state.time.to_u32().unwrap(),
+        enemy_killed,
+        damage: state.damage,
+        prayer_points_used: state.prayer.points_used,
+        prayer_potions_used: state.prayer.potions_used,
+        prayer_ran_out_at: state.prayer.ran_out_at.map(|t| t.to_u32().unwrap()),
+        potions_used: state.potion.used,
+        supply_cost: state.supplies.cost(&config.supplies),
+        supplies_ran_out_at: state.supplies.ran_out_at.map(|t| t.to_u32().unwrap()),
+        gold: state.gold,
+        xp: state.xp(config),
+        died: !state.is_alive(),
+        consumed: state.consumed(config),
+        end: EndState {
+            health: state.player_health as i32,
+            food_stock: config.food.as_ref().map(|_| state.food.stock),
+            potion_stock: config.potion.as_ref().map(|_| state.potion.stock),
+        },
+        summoning: state.summoning.result(&config.summoning),
+        loot: state.loot,
+    };
+    (result, state.trace)
 }
 
-
 pub fn format_fighting_results(results: &[FightingSimResult]) -> String {
     let mut simulations_time_results: Vec<f64> = Vec::new();
     let mut enemy_killed: Vec<f64> = Vec::new();
+    let mut damage = [0.0; 4];
+    let mut prayer_points_used = 0.0;
+    let mut prayer_potions_used = 0.0;
+    let mut potions_used = 0.0;
+    let mut supply_cost = 0.0;
+    let mut supplies_ran_out = 0.0;
+    let mut prayer_ran_out: Vec<f64> = Vec::new();
 
     for r in results {
         simulations_time_results.push(r.time as f64);
         enemy_killed.push(r.enemy_killed as f64);
+        damage[0] += r.damage.normal as f64;
+        damage[1] += r.damage.special as f64;
+        damage[2] += r.damage.reflect as f64;
+        damage[3] += r.damage.lifesteal as f64;
+        prayer_points_used += r.prayer_points_used as f64;
+        prayer_potions_used += r.prayer_potions_used as f64;
+        potions_used += r.potions_used as f64;
+        supply_cost += r.supply_cost;
+        if r.supplies_ran_out_at.is_some() {
+            supplies_ran_out += 1.0;
+        }
+        if let Some(t) = r.prayer_ran_out_at {
+            prayer_ran_out.push(t as f64);
+        }
     }
+    let hours = simulations_time_results.iter().sum::<f64>() / 3600.0;
+    let prayer_ran_out_time = if prayer_ran_out.is_empty() { 0.0 } else { mean(&prayer_ran_out) };
+    let damage_total = (damage[0] + damage[1] + damage[2]).max(1.0);
+    let damage_share = damage.map(|d| d / damage_total * 100.0);
+    let damage = damage.map(|d| d / results.len() as f64);
     let mean_time = format_duration_as_hms(mean(&simulations_time_results));
     let median_time = format_duration_as_hms(median(&simulations_time_results));
 
@@ -162,6 +768,21 @@ pub fn format_fighting_results(results: &[FightingSimResult]) -> String {
         Median killed: {:.2}
         Min killed: {}
         Max killed: {}
+        ------------------------
+        Normal attack damage: {:.2} ({:.1}%)
+        Special attack damage: {:.2} ({:.1}%)
+        Reflected damage: {:.2} ({:.1}%)
+        Lifesteal healing: {:.2}
+        ------------------------
+        Prayer points per hour: {:.2}
+        Prayer potions per hour: {:.2}
+        Prayer ran out: {:.2}%
+        Mean prayer run out time: {}
+        ------------------------
+        Potions per hour: {:.2}
+        Supply cost per hour: {:.2}
+        Supply cost per kill: {:.2}
+        Supplies ran out: {:.2}%
         "#),
         mean_time,
         median_time,
@@ -171,5 +792,135 @@ pub fn format_fighting_results(results: &[FightingSimResult]) -> String {
         median_killed,
         min_killed,
         max_killed,
+        damage[0],
+        damage_share[0],
+        damage[1],
+        damage_share[1],
+        damage[2],
+        damage_share[2],
+        damage[3],
+        prayer_points_used / hours,
+        prayer_potions_used / hours,
+        prayer_ran_out.len() as f64 / results.len() as f64 * 100.0,
+        format_duration_as_hms(prayer_ran_out_time),
+        potions_used / hours,
+        supply_cost / hours,
+        supply_cost / enemy_killed.iter().sum::<f64>().max(1.0),
+        supplies_ran_out / results.len() as f64 * 100.0,
     )
 }
+
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::super::summoning::Familiar;
+    use super::*;
+
+    fn config() -> FightingSimConfig {
+        FightingSimConfig::new(720, 8, 8, 1, 111, 0.76, 3.0, 300, 0, 116, 0.35, 2.4)
+    }
+
+    fn dot(kind: DotKind) -> DamageOverTime {
+        DamageOverTime { kind, damage: 10, ticks: 3, interval: dec!(2) }
+    }
+
+    #[test]
+    fn burns_do_not_stack() {
+        let mut state = FightState::new(&config());
+        state.apply_dot(dot(DotKind::Burn));
+        state.apply_dot(dot(DotKind::Burn));
+        state.tick_dots(dec!(2));
+        assert_eq!(state.player_health, 710);
+    }
+
+    #[test]
+    fn poisons_and_bleeds_stack() {
+        let mut state = FightState::new(&config());
+        state.apply_dot(dot(DotKind::Poison));
+        state.apply_dot(dot(DotKind::Poison));
+        state.apply_dot(dot(DotKind::Bleed));
+        state.tick_dots(dec!(6));
+        assert_eq!(state.player_health, 720 - 90);
+        assert!(state.dots.is_empty());
+    }
+
+    fn summoning(bonus: FamiliarBonus) -> Summoning {
+        Summoning {
+            familiars: vec![Familiar { name: "Wolf".to_string(), bonus, tablets: 1000, charges_per_action: 1 }],
+            synergies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn stunned_attacks_use_no_charges() {
+        let mut config = config();
+        config.summoning = summoning(FamiliarBonus { damage: 0.1, ..FamiliarBonus::default() });
+        let mut state = FightState::new(&config);
+        state.stun_time = dec!(100);
+        fight(&config, &config.enemy, &mut state, dec!(30), &mut StdRng::seed_from_u64(1));
+        assert_eq!(state.summoning.actions, 0);
+        assert_eq!(state.summoning.tablets, vec![1000]);
+    }
+
+    #[test]
+    fn familiar_damage_is_scaled_to_the_damage_dealt() {
+        let mut config = FightingSimConfig::new(720, 8, 8, 100, 100, 1.0, 3.0, 1, 0, 0, 0.0, 2.4);
+        config.summoning = summoning(FamiliarBonus { damage: 0.5, ..FamiliarBonus::default() });
+        let mut state = FightState::new(&config);
+        assert!(fight(&config, &config.enemy, &mut state, dec!(30), &mut StdRng::seed_from_u64(1)));
+        assert_eq!(state.summoning.actions, 1);
+        assert!((state.summoning.contribution.damage - 50.0 / 150.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn familiar_healing_is_capped_at_the_health_regenerated() {
+        let mut config = FightingSimConfig::new(720, 8, 8, 0, 0, 1.0, 3.0, 300, 0, 0, 0.0, 2.4);
+        config.summoning = summoning(FamiliarBonus { regen: 0.5, ..FamiliarBonus::default() });
+        let mut state = FightState::new(&config);
+        fight(&config, &config.enemy, &mut state, dec!(60), &mut StdRng::seed_from_u64(1));
+        assert_eq!(state.summoning.contribution.healing, 0.0);
+
+        config.start_health = Some(718);
+        let mut state = FightState::new(&config);
+        fight(&config, &config.enemy, &mut state, dec!(60), &mut StdRng::seed_from_u64(1));
+        assert_eq!(state.summoning.contribution.healing, 2.0);
+    }
+
+    #[test]
+    fn configs_without_xp_per_damage_use_the_default() {
+        let mut json = serde_json::to_value(config()).unwrap();
+        json.as_object_mut().unwrap().remove("xp_per_damage");
+        let config: FightingSimConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(config.xp_per_damage, XP_PER_DAMAGE);
+    }
+
+    #[test]
+    fn large_damage_sums_saturate() {
+        let mut config = config();
+        config.enemy.hit_chance = 1.0;
+        config.enemy.specials = vec![SpecialAttack {
+            chance: 1.0,
+            hits: 3,
+            damage_min: 30_000,
+            damage_max: 30_000,
+            stun: dec!(0),
+            dot: None,
+            heal: 0,
+        }];
+        let mut state = FightState::new(&config);
+        let mut enemy_health = config.enemy.health;
+        let (damage, raw_damage) = enemy_attack(&config.enemy, &mut enemy_health, 0.0, 0.0, &mut state, &mut StdRng::seed_from_u64(1));
+        assert_eq!(damage, u16::MAX);
+        assert_eq!(raw_damage, 90_000);
+        assert_eq!(state.player_health, 0);
+
+        let mut state = FightState::new(&config);
+        for _ in 0..3 {
+            state.apply_dot(DamageOverTime { kind: DotKind::Poison, damage: 30_000, ticks: 1, interval: dec!(1) });
+        }
+        state.tick_dots(dec!(1));
+        assert_eq!(state.player_health, 0);
+    }
+}
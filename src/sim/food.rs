@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Food {
+    pub name: String,
+    pub heal: u32,
+    pub stock: u32,
+    pub eat_below: f32,  // auto eat when health drops below this part of max health
+}
+
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FoodState {
+    pub stock: u32,
+    pub eaten: u32,
+}
+
+impl FoodState {
+    pub fn new(food: Option<&Food>) -> Self {
+        Self {
+            stock: food.map_or(0, |f| f.stock),
+            eaten: 0,
+        }
+    }
+
+    // Auto eats while health is below the threshold. Returns the health restored.
+    pub fn eat(&mut self, food: Option<&Food>, health: u32, max_health: u32) -> u32 {
+        let food = match food {
+            Some(food) => food,
+            None => return 0,
+        };
+        let threshold = max_health as f32 * food.eat_below;
+        let mut healed = 0;
+        while health > 0 && ((health + healed) as f32) < threshold && self.stock > 0 && food.heal > 0 {
+            healed = (health + healed + food.heal).min(max_health) - health;
+            self.stock -= 1;
+            self.eaten += 1;
+        }
+        healed
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use indoc::indoc;
+use rand::Rng;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use statistical::mean;
+use super::batch::{per_hour, Metric, Metrics};
+use super::format_duration_as_hms;
+use super::profit::{add_item, ItemDrop, Items, Profit};
+use super::session::{level, level_xp};
+
+pub const MAX_TIME: Decimal = dec!(28800);  // 8 hours
+
+
+// A mining rock, depleted after `hp` actions and back after the respawn time
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub hp: u32,
+    pub respawn_time: Decimal,  // in seconds
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mastery {
+    pub xp: f64,  // at the start of the run
+    pub xp_per_action: f64,
+    pub double_chance_per_level: f32,
+    pub node_hp_per_level: f32,  // extra node hp, rounded down
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GatheringSimConfig {
+    pub resource: String,
+    pub action_interval: Decimal,  // in seconds
+    pub xp: i32,  // per action
+    #[serde(default)]
+    pub double_chance: f32,  // chance for twice the resource
+    #[serde(default)]
+    pub extra_chance: f32,  // chance for `extra_amount` more on top
+    #[serde(default)]
+    pub extra_amount: u32,
+    #[serde(default)]
+    pub rare_drops: Vec<ItemDrop>,  // gems, nests, treasure, rolled on every action
+    #[serde(default)]
+    pub node: Option<Node>,
+    #[serde(default)]
+    pub mastery: Option<Mastery>,
+    #[serde(default)]
+    pub max_time: Option<Decimal>,  // MAX_TIME if not set
+}
+
+impl GatheringSimConfig {
+    pub fn new(resource: &str, action_interval: Decimal, xp: i32) -> Self {
+        Self {
+            resource: resource.to_string(),
+            action_interval,
+            xp,
+            double_chance: 0.0,
+            extra_chance: 0.0,
+            extra_amount: 0,
+            rare_drops: Vec::new(),
+            node: None,
+            mastery: None,
+            max_time: None,
+        }
+    }
+
+    pub fn max_time(&self) -> Decimal {
+        self.max_time.unwrap_or(MAX_TIME)
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let json = std::fs::read(path)?;
+        let config: GatheringSimConfig = serde_json::from_slice(&json)?;
+        Ok(config)
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct GatheringSimResult {
+    pub time: u32,
+    pub actions: u32,
+    pub resources: u32,
+    pub xp: f64,
+    pub depletions: u32,
+    pub respawn_wait: f64,  // seconds spent waiting for the node
+    pub start_mastery_xp: f64,
+    pub mastery_xp: f64,  // at the end of the run
+    pub loot: Items,  // resources and rare drops
+    pub consumed: Items,
+}
+
+impl Profit for GatheringSimResult {
+    fn time(&self) -> f64 {
+        self.time as f64
+    }
+
+    fn gold(&self) -> f64 {
+        0.0
+    }
+
+    fn loot(&self) -> &Items {
+        &self.loot
+    }
+
+    fn consumed(&self) -> &Items {
+        &self.consumed
+    }
+}
+
+impl Metrics for GatheringSimResult {
+    fn metric(&self, metric: Metric) -> f64 {
+        let time = self.time as f64;
+        match metric {
+            Metric::Time => time,
+            Metric::GoldPerHour => 0.0,
+            Metric::KillsPerHour => per_hour(self.resources as f64, time),
+            Metric::XpPerHour => per_hour(self.xp, time),
+            Metric::Survival => 1.0,
+        }
+    }
+}
+
+
+pub fn sim(config: &GatheringSimConfig) -> GatheringSimResult {
+    sim_with_rng(config, &mut rand::thread_rng())
+}
+
+// Same as `sim`, with the random source passed in so runs can be seeded
+pub fn sim_with_rng<R: Rng>(config: &GatheringSimConfig, rng: &mut R) -> GatheringSimResult {
+    let mut result = GatheringSimResult::default();
+    let mastery = config.mastery.clone().unwrap_or_default();
+    let mut mastery_xp = mastery.xp;
+    let mut mastery_level = level(mastery_xp);
+    // Checked against on every action instead of working out the level again
+    let mut next_level_xp = level_xp(mastery_level + 1);
+    let node_hp = |mastery_level: u32| {
+        config.node.as_ref().map(|n| n.hp + (mastery_level as f32 * mastery.node_hp_per_level) as u32)
+    };
+    let mut hp_left = node_hp(mastery_level);
+    let mut time = dec!(0);
+
+    let max_time = config.max_time();
+    let interval = config.action_interval.max(dec!(0.1));
+    while time + interval <= max_time {
+        time += interval;
+        result.actions += 1;
+        result.xp += config.xp as f64;
+
+        let mut amount = 1;
+        let double_chance = (config.double_chance + mastery_level as f32 * mastery.double_chance_per_level).min(1.0);
+        if rng.gen::<f32>() < double_chance {
+            amount *= 2;
+        }
+        if rng.gen::<f32>() < config.extra_chance {
+            amount += config.extra_amount;
+        }
+        result.resources += amount;
+        add_item(&mut result.loot, &config.resource, amount);
+        ItemDrop::roll(&config.rare_drops, &mut result.loot, rng);
+
+        mastery_xp += mastery.xp_per_action;
+        if mastery_xp >= next_level_xp {
+            mastery_level = level(mastery_xp);
+            next_level_xp = level_xp(mastery_level + 1);
+        }
+
+        // Mining waits for the rock to respawn once it is depleted
+        if let (Some(node), Some(hp)) = (&config.node, hp_left.as_mut()) {
+            *hp = hp.saturating_sub(1);
+            if *hp == 0 {
+                result.depletions += 1;
+                let wait = node.respawn_time.min(max_time - time);
+                result.respawn_wait += wait.to_f64().unwrap_or_default();
+                time += wait;
+                hp_left = node_hp(mastery_level);
+            }
+        }
+    }
+
+    result.time = time.to_u32().unwrap_or_default();
+    result.start_mastery_xp = mastery.xp;
+    result.mastery_xp = mastery_xp;
+    result
+}
+
+
+// Mean with the 5th and 95th percentiles of the values
+fn distribution(mut values: Vec<f64>) -> String {
+    values.sort_by(f64::total_cmp);
+    let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+    format!("{:.2} ({:.2} - {:.2})", mean(&values), percentile(0.05), percentile(0.95))
+}
+
+
+pub fn format_gathering_results(results: &[GatheringSimResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    let time: Vec<f64> = results.iter().map(|r| r.time as f64).collect();
+    let total_time = time.iter().sum::<f64>();
+    let actions = results.iter().map(|r| r.actions as f64).sum::<f64>();
+    let depletions = results.iter().map(|r| r.depletions as f64).sum::<f64>();
+    let respawn_wait = results.iter().map(|r| r.respawn_wait).sum::<f64>();
+    let start_levels: Vec<f64> = results.iter().map(|r| level(r.start_mastery_xp) as f64).collect();
+    let end_levels: Vec<f64> = results.iter().map(|r| level(r.mastery_xp) as f64).collect();
+
+    let mut output = format!(
+        indoc!(r#"
+        Mean time: {}
+        Actions per hour: {:.2}
+        XP per hour: {}
+        Resources per hour: {}
+        ------------------------
+        Mastery level: {:.2} -> {:.2}
+        Depletions per hour: {:.2}
+        Waiting for respawn: {:.2}%
+        ------------------------
+        "#),
+        format_duration_as_hms(mean(&time)),
+        per_hour(actions, total_time),
+        distribution(results.iter().map(|r| r.metric(Metric::XpPerHour)).collect()),
+        distribution(results.iter().map(|r| r.metric(Metric::KillsPerHour)).collect()),
+        mean(&start_levels),
+        mean(&end_levels),
+        per_hour(depletions, total_time),
+        if total_time > 0.0 { respawn_wait / total_time * 100.0 } else { 0.0 },
+    );
+    let items: BTreeSet<&String> = results.iter().flat_map(|r| r.loot.keys()).collect();
+    for item in items {
+        let per_run = results.iter()
+            .map(|r| per_hour(r.loot.get(item).copied().unwrap_or_default() as f64, r.time as f64))
+            .collect();
+        output += &format!("{} per hour: {}\n", item, distribution(per_run));
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::*;
+
+    fn config() -> GatheringSimConfig {
+        let mut config = GatheringSimConfig::new("Ore", dec!(1), 10);
+        config.max_time = Some(dec!(100));
+        config
+    }
+
+    fn run(config: &GatheringSimConfig) -> GatheringSimResult {
+        sim_with_rng(config, &mut StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn depleted_nodes_wait_for_the_respawn() {
+        let mut config = config();
+        config.node = Some(Node { hp: 5, respawn_time: dec!(5) });
+        let result = run(&config);
+        assert_eq!(result.time, 100);
+        assert_eq!(result.actions, 50);
+        assert_eq!(result.depletions, 10);
+        assert_eq!(result.respawn_wait, 50.0);
+        assert_eq!(result.loot["Ore"], 50);
+        assert_eq!(result.xp, 500.0);
+    }
+
+    #[test]
+    fn rare_drops_are_rolled_on_every_action() {
+        let mut config = config();
+        config.rare_drops = vec![
+            ItemDrop { item: "Gem".to_string(), chance: 1.0, min: 1, max: 1 },
+            ItemDrop { item: "Nest".to_string(), chance: 0.0, min: 1, max: 1 },
+        ];
+        let result = run(&config);
+        assert_eq!(result.loot["Gem"], 100);
+        assert!(!result.loot.contains_key("Nest"));
+        assert_eq!(result.resources, 100);
+    }
+
+    #[test]
+    fn mastery_levels_up_and_raises_the_bonuses() {
+        let mut config = config();
+        config.node = Some(Node { hp: 1, respawn_time: dec!(1) });
+        config.mastery = Some(Mastery {
+            xp: 0.0,
+            xp_per_action: 83.0,
+            double_chance_per_level: 10.0,
+            node_hp_per_level: 1.0,
+        });
+        let result = run(&config);
+        assert_eq!(level(result.start_mastery_xp), 1);
+        assert!(level(result.mastery_xp) > 10);
+        // Every action is doubled and the node lasts longer as the level goes up
+        assert_eq!(result.resources, result.actions * 2);
+        assert!(result.depletions < result.actions / 2);
+    }
+}
@@ -3,6 +3,29 @@ use rust_decimal::prelude::ToPrimitive;
 
 pub mod thieving;
 pub mod fighting;
+pub mod dungeon;
+pub mod slayer;
+pub mod prayer;
+pub mod potion;
+pub mod supplies;
+pub mod food;
+pub mod profit;
+pub mod equipment;
+pub mod batch;
+pub mod optimizer;
+pub mod sweep;
+pub mod sensitivity;
+pub mod compare;
+pub mod breakeven;
+pub mod trace;
+pub mod envelope;
+pub mod offline;
+pub mod session;
+pub mod agility;
+pub mod gathering;
+pub mod artisan;
+pub mod pipeline;
+pub mod summoning;
 
 
 fn format_duration_as_hms(input_seconds: f64) -> String {
@@ -18,4 +41,21 @@ fn format_duration_as_hms(input_seconds: f64) -> String {
         duration.num_minutes() % 60,
         duration.num_seconds() % 60
     )
-}
\ No newline at end of file
+}
+
+fn min_max<T>(values: &[T]) -> (T, T)
+where
+    T: Copy + PartialOrd,
+{
+    let mut min = values[0];
+    let mut max = values[0];
+    for &v in values.iter() {
+        if v.partial_cmp(&min).unwrap() == std::cmp::Ordering::Less {
+            min = v;
+        }
+        if v.partial_cmp(&max).unwrap() == std::cmp::Ordering::Greater {
+            max = v;
+        }
+    }
+    (min, max)
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use super::fighting::FightingSimConfig;
+use super::profit::{add_item, Items};
+use super::thieving::ThievingSimConfig;
+use super::trace::{EventKind, Trace};
+
+pub const DEFAULT_CAP_HOURS: u32 = 12;
+
+
+// Offline progress is capped by the game, bonuses raise the cap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OfflineConfig {
+    pub cap_hours: u32,
+    pub bonus_hours: u32,
+    pub start_health: Option<i32>,  // full health if not set
+    pub food_stock: Option<u32>,  // replaces the stock of the configured food
+    pub potion_stock: Option<u32>,  // replaces the stock of the configured potion
+}
+
+impl Default for OfflineConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAP_HOURS)
+    }
+}
+
+impl OfflineConfig {
+    pub fn new(cap_hours: u32) -> Self {
+        Self {
+            cap_hours,
+            bonus_hours: 0,
+            start_health: None,
+            food_stock: None,
+            potion_stock: None,
+        }
+    }
+
+    pub fn hours(&self) -> u32 {
+        self.cap_hours + self.bonus_hours
+    }
+
+    pub fn apply_thieving(&self, base: &ThievingSimConfig) -> ThievingSimConfig {
+        let mut config = base.clone();
+        config.max_time = Some(Decimal::from(self.hours() * 3600));
+        if let Some(health) = self.start_health {
+            config.start_health = Some(health.min(config.max_health));
+        }
+        if let (Some(food), Some(stock)) = (config.food.as_mut(), self.food_stock) {
+            food.stock = stock;
+        }
+        if let (Some(potion), Some(stock)) = (config.potion.as_mut(), self.potion_stock) {
+            potion.stock = stock;
+        }
+        config
+    }
+
+    pub fn apply_fighting(&self, base: &FightingSimConfig) -> FightingSimConfig {
+        let mut config = base.clone();
+        config.max_time = Some(Decimal::from(self.hours() * 3600));
+        if let Some(health) = self.start_health {
+            config.start_health = Some(health.clamp(0, config.player_health as i32) as u16);
+        }
+        if let (Some(food), Some(stock)) = (config.food.as_mut(), self.food_stock) {
+            food.stock = stock;
+        }
+        if let (Some(potion), Some(stock)) = (config.potion.as_mut(), self.potion_stock) {
+            potion.stock = stock;
+        }
+        config
+    }
+}
+
+
+// What the player finds on return after the given number of hours, averaged over runs
+#[derive(Debug, Default, Clone)]
+pub struct HourMark {
+    pub hour: u32,
+    pub death_chance: f64,
+    pub health: f64,  // dead runs count as zero
+    pub gold: f64,
+    pub loot: BTreeMap<String, f64>,
+}
+
+
+pub fn offline_report<F: FnMut() -> Trace>(iterations: usize, hours: u32, start_health: i32, mut sim: F) -> Vec<HourMark> {
+    let mut marks: Vec<HourMark> = (0..=hours).map(|hour| HourMark { hour, ..HourMark::default() }).collect();
+    for _ in 0..iterations {
+        let trace = sim();
+        let mut events = trace.events.iter().peekable();
+        let mut health = start_health;
+        let mut gold = 0.0;
+        let mut loot = Items::new();
+        let mut died = false;
+        for mark in marks.iter_mut() {
+            let time = mark.hour as f64 * 3600.0;
+            while let Some(event) = events.next_if(|e| e.time <= time) {
+                health = event.health;
+                match &event.kind {
+                    EventKind::Steal { gold: amount, .. } => gold += *amount as f64,
+                    EventKind::Kill { gold: amount } => gold += *amount as f64,
+                    EventKind::Loot { item, amount } => add_item(&mut loot, item, *amount),
+                    EventKind::Death => died = true,
+                    _ => {}
+                }
+            }
+            if died {
+                mark.death_chance += 1.0;
+            }
+            mark.health += health.max(0) as f64;
+            mark.gold += gold;
+            for (item, amount) in &loot {
+                *mark.loot.entry(item.clone()).or_default() += *amount as f64;
+            }
+        }
+    }
+
+    let runs = iterations.max(1) as f64;
+    for mark in marks.iter_mut() {
+        mark.death_chance /= runs;
+        mark.health /= runs;
+        mark.gold /= runs;
+        mark.loot.values_mut().for_each(|amount| *amount /= runs);
+    }
+    marks
+}
+
+
+pub fn format_offline_results(marks: &[HourMark]) -> String {
+    let mut output = format!("{:>4} | {:>12} | {:>8} | {:>12} | loot\n", "hour", "death chance", "health", "gold");
+    for mark in marks {
+        let loot: Vec<String> = mark.loot.iter().map(|(item, amount)| format!("{} x{:.1}", item, amount)).collect();
+        output += &format!(
+            "{:>4} | {:>11.2}% | {:>8.1} | {:>12.0} | {}\n",
+            mark.hour,
+            mark.death_chance * 100.0,
+            mark.health,
+            mark.gold,
+            loot.join(", "),
+        );
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use super::super::trace::Event;
+    use super::*;
+
+    fn trace(events: Vec<(f64, i32, EventKind)>) -> Trace {
+        let mut trace = Trace::recording();
+        trace.events = events.into_iter().map(|(time, health, kind)| Event { time, health, kind }).collect();
+        trace
+    }
+
+    fn report() -> Vec<HourMark> {
+        let mut traces = vec![
+            trace(vec![
+                (1800.0, 80, EventKind::Steal { success: true, gold: 100 }),
+                (3600.0, 70, EventKind::Loot { item: "Gem".to_string(), amount: 1 }),
+                (5000.0, 0, EventKind::Death),
+            ]),
+            trace(vec![(4000.0, 90, EventKind::Kill { gold: 50 })]),
+        ];
+        offline_report(2, 2, 100, || traces.remove(0))
+    }
+
+    #[test]
+    fn marks_every_hour_from_the_start() {
+        let marks = report();
+        assert_eq!(marks.iter().map(|m| m.hour).collect::<Vec<u32>>(), vec![0, 1, 2]);
+        assert_eq!(marks[0].health, 100.0);
+        assert_eq!(marks[0].gold, 0.0);
+        assert!(marks[0].loot.is_empty());
+    }
+
+    #[test]
+    fn averages_deaths_gold_and_loot_over_the_runs() {
+        let marks = report();
+        assert_eq!(marks[1].death_chance, 0.0);
+        assert_eq!(marks[1].health, 85.0);
+        assert_eq!(marks[1].gold, 50.0);
+        assert_eq!(marks[1].loot["Gem"], 0.5);
+        assert_eq!(marks[2].death_chance, 0.5);
+        assert_eq!(marks[2].health, 45.0);
+        assert_eq!(marks[2].gold, 75.0);
+        assert_eq!(marks[2].loot["Gem"], 0.5);
+    }
+
+    #[test]
+    fn applies_the_start_state_to_the_config() {
+        let mut offline = OfflineConfig::new(12);
+        offline.bonus_hours = 2;
+        offline.start_health = Some(10_000);
+        let config = offline.apply_thieving(&ThievingSimConfig::new(dec!(1), 1, 100, dec!(2.6), 0.5, 1, 10, 10, 20));
+        assert_eq!(config.max_time, Some(dec!(50400)));
+        assert_eq!(config.start_health, Some(100));
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use indoc::indoc;
+use super::batch::{run_batch, Estimate, Metric, Metrics};
+use super::equipment::{Item, ItemStats, Loadout, SetBonus, Skill, Slot};
+
+// Above this many combinations the search goes slot by slot instead
+pub const MAX_COMBINATIONS: usize = 2000;
+
+
+#[derive(Debug, Clone)]
+pub struct OptimizerConfig {
+    pub skill: Skill,
+    pub objective: Metric,
+    pub top: usize,
+    pub screen_iterations: usize,  // per loadout, used to shortlist candidates
+    pub iterations: usize,  // per shortlisted loadout
+    pub max_combinations: usize,
+}
+
+impl OptimizerConfig {
+    pub fn new(skill: Skill, objective: Metric, top: usize, iterations: usize) -> Self {
+        Self {
+            skill,
+            objective,
+            top,
+            screen_iterations: (iterations / 10).max(10),
+            iterations,
+            max_combinations: MAX_COMBINATIONS,
+        }
+    }
+
+    // Loadouts kept between slots by the beam search, and shortlisted after screening
+    fn shortlist(&self) -> usize {
+        self.top * 4
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct OptimizerResult {
+    pub loadout: Loadout,
+    pub stats: ItemStats,
+    pub estimate: Estimate,
+}
+
+
+// Items worth trying for every slot. An item is dropped when another item of the
+// same slot dominates it for the skill and it is not part of any set.
+pub fn candidates(inventory: &[Item], sets: &[SetBonus], skill: Skill) -> BTreeMap<Slot, Vec<Item>> {
+    let in_set = |item: &Item| sets.iter().any(|set| set.items.contains(&item.name));
+    let mut by_slot: BTreeMap<Slot, Vec<Item>> = BTreeMap::new();
+    for item in inventory {
+        let items = by_slot.entry(item.slot).or_default();
+        if !items.iter().any(|i| i.name == item.name) {
+            items.push(item.clone());
+        }
+    }
+    for items in by_slot.values_mut() {
+        let all = items.clone();
+        items.retain(|item| {
+            in_set(item) || !all.iter().any(|other| other.stats.dominates(&item.stats, skill))
+        });
+    }
+    by_slot
+}
+
+
+// Loadouts made by trying every option of the slot on each of them
+fn extend(loadouts: &[Loadout], items: &[Item], skill: Skill) -> Vec<Loadout> {
+    // Leaving the slot empty is only worth it if no item is strictly better
+    let keep_empty = !items.iter().any(|item| item.stats.dominates(&ItemStats::default(), skill));
+    let mut next = Vec::new();
+    for loadout in loadouts {
+        if keep_empty {
+            next.push(loadout.clone());
+        }
+        for item in items {
+            let mut loadout = loadout.clone();
+            loadout.equip(item.clone());
+            next.push(loadout);
+        }
+    }
+    // Named once evaluated
+    for loadout in &mut next {
+        loadout.name.clear();
+    }
+    next
+}
+
+fn combination_count(candidates: &BTreeMap<Slot, Vec<Item>>) -> usize {
+    candidates.values().fold(1, |count, items| count.saturating_mul(items.len() + 1))
+}
+
+fn combinations(candidates: &BTreeMap<Slot, Vec<Item>>, skill: Skill) -> Vec<Loadout> {
+    let mut loadouts = vec![Loadout::new("")];
+    for items in candidates.values() {
+        loadouts = extend(&loadouts, items, skill);
+    }
+    loadouts
+}
+
+
+fn evaluate<R, F>(loadouts: Vec<Loadout>, sets: &[SetBonus], objective: Metric, iterations: usize, sim: &F) -> Vec<OptimizerResult>
+where
+    R: Metrics,
+    F: Fn(&ItemStats) -> R,
+{
+    let mut results: Vec<OptimizerResult> = loadouts.into_iter().enumerate().map(|(i, mut loadout)| {
+        if loadout.name.is_empty() {
+            loadout.name = format!("Loadout {}", i + 1);
+        }
+        let stats = loadout.stats(sets);
+        let runs = run_batch(iterations, || sim(&stats));
+        OptimizerResult {
+            loadout,
+            stats,
+            estimate: Estimate::from_results(&runs, objective),
+        }
+    }).collect();
+    results.sort_by(|a, b| b.estimate.mean.total_cmp(&a.estimate.mean));
+    results
+}
+
+
+// Fills one slot at a time, keeping only the best partial loadouts after each slot.
+// Set bonuses that need items from slots filled later can be missed.
+fn beam_search<R, F>(candidates: &BTreeMap<Slot, Vec<Item>>, sets: &[SetBonus], config: &OptimizerConfig, sim: &F) -> Vec<OptimizerResult>
+where
+    R: Metrics,
+    F: Fn(&ItemStats) -> R,
+{
+    let mut beam = vec![Loadout::new("")];
+    let mut screened = Vec::new();
+    for items in candidates.values() {
+        screened = evaluate(extend(&beam, items, config.skill), sets, config.objective, config.screen_iterations, sim);
+        screened.truncate(config.shortlist());
+        beam = screened.iter().map(|r| r.loadout.clone()).collect();
+    }
+    screened
+}
+
+
+// Searches gear combinations from the inventory and returns the best loadouts by
+// the objective. Every loadout is screened with a short batch first, the best of
+// them are then run again with the full iteration count. Inventories with more
+// combinations than the cap are searched with a beam search instead.
+pub fn optimize<R, F>(inventory: &[Item], sets: &[SetBonus], config: &OptimizerConfig, sim: F) -> Vec<OptimizerResult>
+where
+    R: Metrics,
+    F: Fn(&ItemStats) -> R,
+{
+    let candidates = candidates(inventory, sets, config.skill);
+    let mut screened = if combination_count(&candidates) <= config.max_combinations {
+        evaluate(combinations(&candidates, config.skill), sets, config.objective, config.screen_iterations, &sim)
+    } else {
+        beam_search(&candidates, sets, config, &sim)
+    };
+    screened.truncate(config.shortlist());
+
+    let shortlist = screened.into_iter().map(|r| r.loadout).collect();
+    let mut results = evaluate(shortlist, sets, config.objective, config.iterations, &sim);
+    results.truncate(config.top);
+    results
+}
+
+
+pub fn format_optimizer_results(results: &[OptimizerResult], objective: Metric) -> String {
+    let mut output = String::new();
+    for (i, result) in results.iter().enumerate() {
+        let items = result.loadout.items.values().map(|item| item.name.as_str()).collect::<Vec<_>>();
+        output += &format!(
+            indoc!(r#"
+            #{} {}: {}
+            Items: {}
+            "#),
+            i + 1,
+            objective,
+            result.estimate,
+            items.join(", "),
+        );
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Score(f64);
+
+    impl Metrics for Score {
+        fn metric(&self, _metric: Metric) -> f64 {
+            self.0
+        }
+    }
+
+    fn item(name: &str, slot: Slot, stats: ItemStats) -> Item {
+        Item { name: name.to_string(), slot, stats }
+    }
+
+    #[test]
+    fn prunes_only_on_the_skill_stats() {
+        let inventory = vec![
+            item("Good", Slot::Ring, ItemStats { steal_chance: 0.1, ..ItemStats::default() }),
+            item("Brawler", Slot::Ring, ItemStats { steal_chance: 0.05, damage_min: 50, ..ItemStats::default() }),
+        ];
+        assert_eq!(candidates(&inventory, &[], Skill::Thieving)[&Slot::Ring][0].name, "Good");
+        assert_eq!(candidates(&inventory, &[], Skill::Fighting)[&Slot::Ring][0].name, "Brawler");
+        assert_eq!(candidates(&inventory, &[], Skill::Fighting)[&Slot::Ring].len(), 1);
+    }
+
+    #[test]
+    fn large_inventories_fall_back_to_beam_search() {
+        let slots = [Slot::Helmet, Slot::Body, Slot::Legs, Slot::Boots, Slot::Gloves, Slot::Cape, Slot::Amulet, Slot::Ring];
+        let mut inventory = Vec::new();
+        for slot in slots {
+            // Trade-offs between gold and steal chance, so none of them is pruned
+            for i in 1..=5 {
+                let stats = ItemStats { gold_bonus: i as f32 * 0.01, steal_chance: (6 - i) as f32 * 0.001, ..ItemStats::default() };
+                inventory.push(item(&format!("{:?} {}", slot, i), slot, stats));
+            }
+        }
+        let config = OptimizerConfig::new(Skill::Thieving, Metric::GoldPerHour, 1, 10);
+        assert!(combination_count(&candidates(&inventory, &[], Skill::Thieving)) > config.max_combinations);
+
+        let results = optimize(&inventory, &[], &config, |stats| Score(stats.gold_bonus as f64));
+        assert_eq!(results.len(), 1);
+        assert!((results[0].estimate.mean - 0.4).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use super::profit::{Items, Profit};
+
+
+// Mean items produced and consumed per hour by one skill, taken from a batch of its sims
+#[derive(Debug, Clone, Default)]
+pub struct Stage {
+    pub name: String,
+    pub produced: BTreeMap<String, f64>,
+    pub consumed: BTreeMap<String, f64>,
+}
+
+impl Stage {
+    pub fn from_results<R: Profit>(name: &str, results: &[R]) -> Self {
+        let hours = results.iter().map(|r| r.time()).sum::<f64>() / 3600.0;
+        let rates = |items: fn(&R) -> &Items| {
+            let mut rates: BTreeMap<String, f64> = BTreeMap::new();
+            if hours > 0.0 {
+                for (item, amount) in results.iter().flat_map(items) {
+                    *rates.entry(item.clone()).or_insert(0.0) += *amount as f64 / hours;
+                }
+            }
+            rates
+        };
+        Self {
+            name: name.to_string(),
+            produced: rates(|r| r.loot()),
+            consumed: rates(|r| r.consumed()),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct StageHours {
+    pub name: String,
+    pub hours: f64,
+    pub supplies: Vec<String>,  // items this stage makes for the stages after it
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub target: String,
+    pub target_hours: f64,
+    pub stages: Vec<StageHours>,  // in the order they were given
+    pub unsupplied: BTreeMap<String, f64>,  // needed but made by no stage, per target hour
+}
+
+impl Pipeline {
+    // The stage that needs the most hours to keep up
+    pub fn bottleneck(&self) -> Option<&StageHours> {
+        self.stages.iter().filter(|s| s.hours > 0.0).max_by(|a, b| a.hours.total_cmp(&b.hours))
+    }
+}
+
+
+// Works out how many hours every stage has to run to supply the target for the
+// given hours. Stages are listed producers first, e.g. fishing then cooking, and
+// every item is supplied by the closest stage before the one that needs it.
+pub fn solve(stages: &[Stage], target: &Stage, target_hours: f64) -> Result<Pipeline, String> {
+    // Everything is reported per target hour
+    if target_hours.is_nan() || target_hours <= 0.0 {
+        return Err(format!("Target hours have to be above 0, got {}", target_hours));
+    }
+    let mut demand: BTreeMap<String, f64> = target.consumed.iter().map(|(item, rate)| (item.clone(), rate * target_hours)).collect();
+    let mut hours = Vec::new();
+    for stage in stages.iter().rev() {
+        let supplies: Vec<String> = demand.keys()
+            .filter(|item| stage.produced.get(*item).is_some_and(|rate| *rate > 0.0))
+            .cloned()
+            .collect();
+        // The stage runs until the scarcest of its items is covered
+        let stage_hours = supplies.iter().map(|item| demand[item] / stage.produced[item]).fold(0.0, f64::max);
+        for item in &supplies {
+            demand.remove(item);
+        }
+        for (item, rate) in &stage.consumed {
+            *demand.entry(item.clone()).or_insert(0.0) += rate * stage_hours;
+        }
+        hours.push(StageHours {
+            name: stage.name.clone(),
+            hours: stage_hours,
+            supplies,
+        });
+    }
+    hours.reverse();
+    demand.retain(|_, amount| *amount > 0.0);
+
+    Ok(Pipeline {
+        target: target.name.clone(),
+        target_hours,
+        stages: hours,
+        unsupplied: demand.into_iter().map(|(item, amount)| (item, amount / target_hours)).collect(),
+    })
+}
+
+
+pub fn format_pipeline_results(pipeline: &Pipeline) -> String {
+    let mut output = format!("To sustain {:.2} hours of {}:\n", pipeline.target_hours, pipeline.target);
+    output += &format!("{:>12} | {:>10} | {:>15} | supplies\n", "stage", "hours", "per target hour");
+    for stage in &pipeline.stages {
+        output += &format!(
+            "{:>12} | {:>10.2} | {:>15.2} | {}\n",
+            stage.name,
+            stage.hours,
+            stage.hours / pipeline.target_hours,
+            stage.supplies.join(", "),
+        );
+    }
+    let total: f64 = pipeline.stages.iter().map(|s| s.hours).sum();
+    output += &format!("Total skilling hours: {:.2}\n", total);
+    if let Some(bottleneck) = pipeline.bottleneck() {
+        output += &format!("Bottleneck: {} ({:.2} hours)\n", bottleneck.name, bottleneck.hours);
+    }
+    if !pipeline.unsupplied.is_empty() {
+        let items: Vec<String> = pipeline.unsupplied.iter().map(|(item, rate)| format!("{} x{:.1}", item, rate)).collect();
+        output += &format!("Not made by any stage, per target hour: {}\n", items.join(", "));
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(name: &str, produced: &[(&str, f64)], consumed: &[(&str, f64)]) -> Stage {
+        let rates = |items: &[(&str, f64)]| items.iter().map(|(item, rate)| (item.to_string(), *rate)).collect();
+        Stage {
+            name: name.to_string(),
+            produced: rates(produced),
+            consumed: rates(consumed),
+        }
+    }
+
+    fn chain() -> (Vec<Stage>, Stage) {
+        let stages = vec![
+            stage("fishing", &[("Raw Lobster", 100.0)], &[]),
+            stage("cooking", &[("Lobster", 100.0)], &[("Raw Lobster", 120.0)]),
+        ];
+        (stages, stage("fighting", &[("Bones", 50.0)], &[("Lobster", 50.0), ("Potion", 1.0)]))
+    }
+
+    #[test]
+    fn solves_the_chain_back_from_the_target() {
+        let (stages, target) = chain();
+        let pipeline = solve(&stages, &target, 2.0).unwrap();
+        assert_eq!(pipeline.stages[0].name, "fishing");
+        assert!((pipeline.stages[0].hours - 1.2).abs() < 1e-9);
+        assert_eq!(pipeline.stages[0].supplies, vec!["Raw Lobster"]);
+        assert!((pipeline.stages[1].hours - 1.0).abs() < 1e-9);
+        assert_eq!(pipeline.bottleneck().unwrap().name, "fishing");
+        assert_eq!(pipeline.unsupplied, BTreeMap::from([("Potion".to_string(), 1.0)]));
+    }
+
+    #[test]
+    fn rejects_non_positive_target_hours() {
+        let (stages, target) = chain();
+        assert!(solve(&stages, &target, 0.0).is_err());
+        assert!(solve(&stages, &target, -1.0).is_err());
+        assert!(solve(&stages, &target, f64::NAN).is_err());
+    }
+}
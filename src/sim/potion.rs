@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PotionEffect {
+    SuccessChance(f32),  // added to the steal success chance or the player hit chance
+    Damage(f32),  // 0.1 is +10% damage
+    Regen(f32),  // 0.5 is +50% health regeneration
+    DamageReduction(f32),  // 0.1 is -10% damage taken
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Potion {
+    pub name: String,
+    pub effect: PotionEffect,
+    pub charges: u32,  // actions per potion
+    pub stock: u32,
+}
+
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PotionBonus {
+    pub success_chance: f32,
+    pub damage: f32,
+    pub regen: f32,
+    pub damage_reduction: f32,
+}
+
+impl From<PotionEffect> for PotionBonus {
+    fn from(effect: PotionEffect) -> Self {
+        let mut bonus = Self::default();
+        match effect {
+            PotionEffect::SuccessChance(value) => bonus.success_chance = value,
+            PotionEffect::Damage(value) => bonus.damage = value,
+            PotionEffect::Regen(value) => bonus.regen = value,
+            PotionEffect::DamageReduction(value) => bonus.damage_reduction = value,
+        }
+        bonus
+    }
+}
+
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PotionState {
+    pub stock: u32,
+    pub charges_left: u32,
+    pub used: u32,
+}
+
+impl PotionState {
+    pub fn new(potion: Option<&Potion>) -> Self {
+        Self {
+            stock: potion.map_or(0, |p| p.stock),
+            ..Self::default()
+        }
+    }
+
+    // Bonus of the potion currently in effect, without using a charge
+    pub fn bonus(&self, potion: Option<&Potion>) -> PotionBonus {
+        match potion {
+            Some(potion) if self.charges_left > 0 => potion.effect.into(),
+            _ => PotionBonus::default(),
+        }
+    }
+
+    // Uses one charge for an action, drinking a new potion from the stock when
+    // the previous one has run out
+    pub fn use_charge(&mut self, potion: Option<&Potion>) -> PotionBonus {
+        let potion = match potion {
+            Some(potion) => potion,
+            None => return PotionBonus::default(),
+        };
+        if self.charges_left == 0 && self.stock > 0 {
+            self.stock -= 1;
+            self.used += 1;
+            self.charges_left = potion.charges;
+        }
+        if self.charges_left == 0 {
+            return PotionBonus::default();
+        }
+        self.charges_left -= 1;
+        potion.effect.into()
+    }
+}
@@ -0,0 +1,92 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Prayer {
+    pub name: String,
+    pub point_cost: u32,  // per player attack
+    pub accuracy_bonus: f32,  // added to the player hit chance
+    pub damage_bonus: f32,  // 0.1 is +10% damage
+    pub defence_bonus: f32,  // 0.1 reduces the enemy hit chance by 10%
+    pub heal_bonus: f32,  // 0.5 is +50% health regeneration
+}
+
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PrayerBonus {
+    pub accuracy: f32,
+    pub damage: f32,
+    pub defence: f32,
+    pub heal: f32,
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrayerConfig {
+    pub active: Vec<Prayer>,
+    pub points: u32,
+    pub restore_potions: u32,
+    pub points_per_potion: u32,
+    pub potion_name: String,
+}
+
+impl PrayerConfig {
+    pub fn point_cost(&self) -> u32 {
+        self.active.iter().map(|p| p.point_cost).sum()
+    }
+
+    pub fn bonus(&self) -> PrayerBonus {
+        let mut bonus = PrayerBonus::default();
+        for prayer in &self.active {
+            bonus.accuracy += prayer.accuracy_bonus;
+            bonus.damage += prayer.damage_bonus;
+            bonus.defence += prayer.defence_bonus;
+            bonus.heal += prayer.heal_bonus;
+        }
+        bonus
+    }
+}
+
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PrayerState {
+    pub points: u32,
+    pub potions: u32,
+    pub points_used: u32,
+    pub potions_used: u32,
+    pub ran_out_at: Option<Decimal>,
+}
+
+impl PrayerState {
+    pub fn new(config: &PrayerConfig) -> Self {
+        Self {
+            points: config.points,
+            potions: config.restore_potions,
+            ..Self::default()
+        }
+    }
+
+    // Pays for one attack, drinking restore potions when the pool is short.
+    // Returns the bonus of the active prayers, or no bonus once the points are gone.
+    pub fn pray(&mut self, config: &PrayerConfig, time: Decimal) -> PrayerBonus {
+        let cost = config.point_cost();
+        if cost == 0 || self.ran_out_at.is_some() {
+            return PrayerBonus::default();
+        }
+        while self.points < cost && self.potions > 0 && config.points_per_potion > 0 {
+            self.points = (self.points + config.points_per_potion).min(config.points.max(cost));
+            self.potions -= 1;
+            self.potions_used += 1;
+        }
+        if self.points < cost {
+            self.ran_out_at = Some(time);
+            return PrayerBonus::default();
+        }
+        self.points -= cost;
+        self.points_used += cost;
+        config.bonus()
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use indoc::indoc;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PRICE_FILE: &str = "prices.json";
+
+pub type Items = BTreeMap<String, u32>;
+
+
+pub fn add_item(items: &mut Items, name: &str, count: u32) {
+    if count > 0 {
+        *items.entry(name.to_string()).or_insert(0) += count;
+    }
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ItemDrop {
+    pub item: String,
+    pub chance: f32,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl ItemDrop {
+    // Adds the rolled drops to the loot and returns them
+    pub fn roll<R: Rng>(drops: &[ItemDrop], loot: &mut Items, rng: &mut R) -> Items {
+        let mut dropped = Items::new();
+        for drop in drops {
+            if rng.gen::<f32>() < drop.chance {
+                let amount = rng.gen_range(drop.min..=drop.max.max(drop.min));
+                add_item(loot, &drop.item, amount);
+                add_item(&mut dropped, &drop.item, amount);
+            }
+        }
+        dropped
+    }
+}
+
+
+// Item sell prices, stored as a JSON object of item name to price
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PriceList {
+    pub prices: HashMap<String, u32>,
+}
+
+impl PriceList {
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let json = std::fs::read(path)?;
+        let prices: PriceList = serde_json::from_slice(&json)?;
+        Ok(prices)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        let json = serde_json::to_string_pretty(&self)?;
+        std::fs::write(path, json)
+    }
+
+    pub fn price(&self, item: &str) -> u32 {
+        self.prices.get(item).copied().unwrap_or_default()
+    }
+
+    pub fn value(&self, items: &Items) -> f64 {
+        items.iter().map(|(item, count)| self.price(item) as f64 * *count as f64).sum()
+    }
+}
+
+
+pub trait Profit {
+    fn time(&self) -> f64;  // in seconds
+    fn gold(&self) -> f64;
+    fn loot(&self) -> &Items;
+    fn consumed(&self) -> &Items;
+
+    fn net_profit(&self, prices: &PriceList) -> f64 {
+        self.gold() + prices.value(self.loot()) - prices.value(self.consumed())
+    }
+}
+
+
+pub fn format_profit_results<P: Profit>(results: &[P], prices: &PriceList) -> String {
+    let hours = results.iter().map(|r| r.time()).sum::<f64>() / 3600.0;
+    if hours <= 0.0 {
+        return String::new();
+    }
+    let gold = results.iter().map(|r| r.gold()).sum::<f64>();
+    let loot = results.iter().map(|r| prices.value(r.loot())).sum::<f64>();
+    let consumed = results.iter().map(|r| prices.value(r.consumed())).sum::<f64>();
+    let net = gold + loot - consumed;
+
+    format!(
+        indoc!(r#"
+        Gold per hour: {:.2}
+        Loot value per hour: {:.2}
+        Consumed value per hour: {:.2}
+        Net GP per hour: {:.2}
+        Mean net profit: {:.2}
+        "#),
+        gold / hours,
+        loot / hours,
+        consumed / hours,
+        net / hours,
+        net / results.len() as f64,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use super::*;
+
+    fn drop(min: u32, max: u32) -> ItemDrop {
+        ItemDrop { item: "Gem".to_string(), chance: 1.0, min, max }
+    }
+
+    #[test]
+    fn rolls_amounts_between_min_and_max() {
+        let mut loot = Items::new();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let amount = ItemDrop::roll(&[drop(2, 4)], &mut loot, &mut rng)["Gem"];
+            assert!((2..=4).contains(&amount));
+        }
+    }
+
+    #[test]
+    fn max_below_min_drops_the_min() {
+        let mut loot = Items::new();
+        let dropped = ItemDrop::roll(&[drop(5, 1)], &mut loot, &mut StdRng::seed_from_u64(1));
+        assert_eq!(dropped["Gem"], 5);
+        assert_eq!(loot["Gem"], 5);
+    }
+}
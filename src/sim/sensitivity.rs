@@ -0,0 +1,125 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use super::batch::{Estimate, Metric, Metrics};
+use super::sweep::{get_field, is_decimal_field, set_field, DECIMAL_STEP};
+
+// Inputs are moved by this fraction of their value either side
+const STEP_FRACTION: f64 = 0.05;
+
+
+#[derive(Debug, Clone)]
+pub struct Sensitivity {
+    pub field: String,
+    pub value: f64,
+    pub step: f64,
+    pub slope: Estimate,  // change of the metric per unit of the field
+}
+
+impl Sensitivity {
+    // Change of the metric for a 1% change of the field
+    pub fn per_percent(&self) -> f64 {
+        self.slope.mean * self.value.abs() * 0.01
+    }
+}
+
+
+// Moves the field by `step` and reads back the value the config really got, since
+// integer fields are rounded and Decimal fields are snapped to the grid
+fn shifted<C: Serialize + DeserializeOwned>(config: &C, field: &str, value: f64) -> Result<(C, f64), String> {
+    let shifted = set_field(config, field, value)?;
+    let actual = get_field(&shifted, field)?;
+    Ok((shifted, actual))
+}
+
+
+fn field_sensitivity<C, R, F>(config: &C, field: &str, metric: Metric, iterations: usize, seed: u64, sim: &F) -> Result<Sensitivity, String>
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let value = get_field(config, field)?;
+    let mut step = if value == 0.0 { 0.01 } else { value.abs() * STEP_FRACTION };
+    // Whole grid steps, a smaller one would round back to the same interval
+    if is_decimal_field(config, field)? {
+        step = (step / DECIMAL_STEP).round().max(1.0) * DECIMAL_STEP;
+    }
+    let (mut high, mut high_value) = shifted(config, field, value + step)?;
+    if high_value == value {
+        step = 1.0;
+        (high, high_value) = shifted(config, field, value + step)?;
+    }
+    // Fields that start at zero or above are kept from going negative
+    let low_target = if value >= 0.0 { (value - step).max(0.0) } else { value - step };
+    let (low, low_value) = shifted(config, field, low_target)?;
+    if high_value == low_value {
+        return Err(format!("Field {} did not change when stepped", field));
+    }
+
+    // Both sides use the same seed for every run so the difference is not drowned in noise
+    let slopes: Vec<f64> = (0..iterations as u64).map(|i| {
+        let high_result = sim(&high, &mut StdRng::seed_from_u64(seed + i));
+        let low_result = sim(&low, &mut StdRng::seed_from_u64(seed + i));
+        (high_result.metric(metric) - low_result.metric(metric)) / (high_value - low_value)
+    }).collect();
+    Ok(Sensitivity {
+        field: field.to_string(),
+        value,
+        step: high_value - low_value,
+        slope: Estimate::new(&slopes),
+    })
+}
+
+
+// Finite difference of the metric for every field, using common random numbers.
+// Results are sorted by the effect of a 1% change, largest first.
+pub fn sensitivity<C, R, F>(config: &C, fields: &[String], metric: Metric, iterations: usize, sim: F) -> Result<Vec<Sensitivity>, String>
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C, &mut StdRng) -> R,
+{
+    let seed = rand::random::<u32>() as u64;
+    let mut results = fields.iter()
+        .map(|field| field_sensitivity(config, field, metric, iterations, seed, &sim))
+        .collect::<Result<Vec<_>, String>>()?;
+    results.sort_by(|a, b| b.per_percent().abs().total_cmp(&a.per_percent().abs()));
+    Ok(results)
+}
+
+
+pub fn format_sensitivity_results(results: &[Sensitivity], metric: Metric) -> String {
+    let mut output = format!(
+        "{:<28} | {:>10} | {:>36} | {:>10}\n",
+        "field", "value", format!("{} per unit", metric), "per 1%",
+    );
+    for result in results {
+        output += &format!(
+            "{:<28} | {:>10.2} | {:>36} | {:>10.2}\n",
+            result.field,
+            result.value,
+            result.slope.to_string(),
+            result.per_percent(),
+        );
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use crate::thieving::{sim_with_rng, ThievingSimConfig};
+    use super::*;
+
+    #[test]
+    fn decimal_fields_move_by_whole_grid_steps() {
+        let mut config = ThievingSimConfig::new(dec!(1), 1, 100, dec!(1.3), 0.5, 1, 10, 10, 20);
+        config.max_time = Some(dec!(600));
+        let result = field_sensitivity(&config, "steal_interval", Metric::GoldPerHour, 5, 1, &|c, rng| sim_with_rng(c, rng)).unwrap();
+        assert!((result.step - 0.2).abs() < 1e-9);
+        assert!(result.slope.mean < 0.0);
+    }
+}
@@ -0,0 +1,249 @@
+use std::str::FromStr;
+use super::offline::OfflineConfig;
+use super::profit::Profit;
+
+const MAX_LEVEL: u32 = 99;
+
+
+// State at the end of a run that carries over into the next session
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct EndState {
+    pub health: i32,
+    pub food_stock: Option<u32>,
+    pub potion_stock: Option<u32>,  // unopened potions, charges left in an open one are lost
+}
+
+
+pub trait Chained: Profit {
+    fn end_state(&self) -> EndState;
+    fn xp(&self) -> f64;
+    fn died(&self) -> bool;
+}
+
+
+// One block of play, offline blocks are cut to the offline cap
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub offline: bool,
+    pub hours: u32,
+}
+
+// Parses `online:2` or `offline:8`
+impl FromStr for Session {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid session {}, expected online:<hours> or offline:<hours>", s);
+        let (kind, hours) = s.split_once(':').ok_or_else(error)?;
+        let offline = match kind {
+            "online" => false,
+            "offline" => true,
+            _ => return Err(error()),
+        };
+        Ok(Self {
+            offline,
+            hours: hours.parse().map_err(|_| error())?,
+        })
+    }
+}
+
+impl Session {
+    fn name(&self) -> &str {
+        if self.offline { "offline" } else { "online" }
+    }
+
+    // Hours that are really played, offline progress stops at the cap
+    pub fn played_hours(&self, offline_cap_hours: u32) -> u32 {
+        if self.offline { self.hours.min(offline_cap_hours) } else { self.hours }
+    }
+}
+
+
+// Level for the xp, on the usual curve where level 99 takes 13,034,431 xp
+pub fn level(xp: f64) -> u32 {
+    let mut points = 0.0;
+    for level in 1..MAX_LEVEL {
+        points += (level as f64 + 300.0 * 2f64.powf(level as f64 / 7.0)).floor();
+        if (points / 4.0).floor() > xp {
+            return level;
+        }
+    }
+    MAX_LEVEL
+}
+
+// Xp needed to reach the level, the inverse of `level`. Infinite past the cap.
+pub fn level_xp(level: u32) -> f64 {
+    if level > MAX_LEVEL {
+        return f64::INFINITY;
+    }
+    let points: f64 = (1..level).map(|l| (l as f64 + 300.0 * 2f64.powf(l as f64 / 7.0)).floor()).sum();
+    (points / 4.0).floor()
+}
+
+
+// Runs the sessions back to back for every iteration, each session starting from
+// the state the previous one ended in. `run` simulates one session with the start
+// state and length set in the offline config. Returns the results by session.
+pub fn chain<R, F>(sessions: &[Session], offline_cap_hours: u32, iterations: usize, mut run: F) -> Vec<Vec<R>>
+where
+    R: Chained,
+    F: FnMut(&OfflineConfig) -> R,
+{
+    let mut results: Vec<Vec<R>> = sessions.iter().map(|_| Vec::new()).collect();
+    for _ in 0..iterations {
+        let mut start = OfflineConfig::new(0);
+        for (session, session_results) in sessions.iter().zip(results.iter_mut()) {
+            start.cap_hours = session.played_hours(offline_cap_hours);
+            let result = run(&start);
+            let end = result.end_state();
+            // After a death the next session starts from full health
+            start.start_health = if result.died() { None } else { Some(end.health) };
+            start.food_stock = end.food_stock;
+            start.potion_stock = end.potion_stock;
+            session_results.push(result);
+        }
+    }
+    results
+}
+
+
+pub fn format_session_results<R: Chained>(sessions: &[Session], results: &[Vec<R>], start_xp: f64, offline_cap_hours: u32) -> String {
+    let mut output = format!(
+        "{:<12} | {:>5} | {:>8} | {:>10} | {:>10} | {:>12} | {:>12} | {:>5}\n",
+        "session", "hours", "died", "gold", "xp", "total gold", "total xp", "level",
+    );
+    let (mut total_gold, mut total_xp) = (0.0, start_xp);
+    for (i, (session, runs)) in sessions.iter().zip(results).enumerate() {
+        let count = runs.len().max(1) as f64;
+        let gold = runs.iter().map(|r| r.gold()).sum::<f64>() / count;
+        let xp = runs.iter().map(|r| r.xp()).sum::<f64>() / count;
+        let died = runs.iter().filter(|r| r.died()).count() as f64 / count;
+        total_gold += gold;
+        total_xp += xp;
+        output += &format!(
+            "{:<12} | {:>5} | {:>7.2}% | {:>10.0} | {:>10.0} | {:>12.0} | {:>12.0} | {:>5}\n",
+            format!("{} {}", i + 1, session.name()),
+            session.played_hours(offline_cap_hours),
+            died * 100.0,
+            gold,
+            xp,
+            total_gold,
+            total_xp,
+            level(total_xp),
+        );
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::super::profit::Items;
+    use super::*;
+
+    // Loses 100 health, one food and one potion every hour and dies at 0 health
+    #[derive(Default)]
+    struct Run {
+        start: OfflineConfig,
+        end: EndState,
+        died: bool,
+        items: Items,
+    }
+
+    impl Profit for Run {
+        fn time(&self) -> f64 {
+            self.start.cap_hours as f64 * 3600.0
+        }
+
+        fn gold(&self) -> f64 {
+            self.start.cap_hours as f64 * 10.0
+        }
+
+        fn loot(&self) -> &Items {
+            &self.items
+        }
+
+        fn consumed(&self) -> &Items {
+            &self.items
+        }
+    }
+
+    impl Chained for Run {
+        fn end_state(&self) -> EndState {
+            self.end
+        }
+
+        fn xp(&self) -> f64 {
+            self.start.cap_hours as f64 * 1000.0
+        }
+
+        fn died(&self) -> bool {
+            self.died
+        }
+    }
+
+    fn run(start: &OfflineConfig) -> Run {
+        let hours = start.cap_hours;
+        let health = start.start_health.unwrap_or(500) - 100 * hours as i32;
+        Run {
+            start: start.clone(),
+            end: EndState {
+                health: health.max(0),
+                food_stock: Some(start.food_stock.unwrap_or(10).saturating_sub(hours)),
+                potion_stock: Some(start.potion_stock.unwrap_or(5).saturating_sub(hours)),
+            },
+            died: health <= 0,
+            ..Run::default()
+        }
+    }
+
+    fn sessions(s: &str) -> Vec<Session> {
+        s.split(' ').map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn levels_follow_the_xp_curve() {
+        assert_eq!(level(0.0), 1);
+        assert_eq!(level(82.0), 1);
+        assert_eq!(level(83.0), 2);
+        assert_eq!(level(13_034_430.0), 98);
+        assert_eq!(level(13_034_431.0), 99);
+        assert_eq!(level(f64::MAX), 99);
+        assert_eq!(level_xp(1), 0.0);
+        assert_eq!(level_xp(2), 83.0);
+        assert_eq!(level_xp(99), 13_034_431.0);
+        assert_eq!(level_xp(100), f64::INFINITY);
+        for l in 1..=99 {
+            assert_eq!(level(level_xp(l)), l);
+        }
+    }
+
+    #[test]
+    fn state_carries_over_between_sessions() {
+        let results = chain(&sessions("online:1 offline:24 online:1"), 2, 1, run);
+        let starts: Vec<&OfflineConfig> = results.iter().map(|r| &r[0].start).collect();
+        assert_eq!(starts[0].start_health, None);
+        assert_eq!(starts[1].cap_hours, 2);
+        assert_eq!(starts[1].start_health, Some(400));
+        assert_eq!(starts[1].food_stock, Some(9));
+        assert_eq!(starts[2].start_health, Some(200));
+        assert_eq!(starts[2].food_stock, Some(7));
+        assert_eq!(starts[2].potion_stock, Some(2));
+    }
+
+    #[test]
+    fn a_death_resets_health() {
+        let results = chain(&sessions("online:6 online:1"), 12, 1, run);
+        assert!(results[0][0].died);
+        assert_eq!(results[1][0].start.start_health, None);
+    }
+
+    #[test]
+    fn offline_sessions_show_the_capped_hours() {
+        let sessions = sessions("offline:24");
+        let results = chain(&sessions, 12, 2, run);
+        let output = format_session_results(&sessions, &results, 0.0, 12);
+        assert!(output.contains("1 offline    |    12 |"));
+        assert!(output.contains("12000"));
+    }
+}
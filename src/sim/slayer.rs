@@ -0,0 +1,212 @@
+use indoc::indoc;
+use rand::Rng;
+use rand::distributions::{Distribution, WeightedIndex};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use statistical::mean;
+use super::batch::{per_hour, Metric, Metrics};
+use super::fighting::{fight, FightState, FightingSimConfig, Monster, RESPAWN_TIME};
+use super::format_duration_as_hms;
+use super::profit::{Items, Profit};
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SlayerTask {
+    pub monster: Monster,
+    pub weight: u32,
+    pub min_kills: u32,
+    pub max_kills: u32,
+    pub coins_per_kill: u32,
+    pub xp_per_kill: u32,
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SlayerTier {
+    pub tasks: Vec<SlayerTask>,
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct SlayerSimResult {
+    pub time: u32,
+    pub enemy_killed: u32,
+    pub tasks_completed: u32,
+    pub task_time: u32,  // sum of the completed tasks durations
+    pub coins: u32,
+    pub xp: u32,
+    pub died: bool,
+    pub gold: u32,
+    pub loot: Items,
+    pub consumed: Items,
+}
+
+impl Profit for SlayerSimResult {
+    fn time(&self) -> f64 {
+        self.time as f64
+    }
+
+    fn gold(&self) -> f64 {
+        self.gold as f64
+    }
+
+    fn loot(&self) -> &Items {
+        &self.loot
+    }
+
+    fn consumed(&self) -> &Items {
+        &self.consumed
+    }
+}
+
+impl Metrics for SlayerSimResult {
+    fn metric(&self, metric: Metric) -> f64 {
+        let time = self.time as f64;
+        match metric {
+            Metric::Time => time,
+            Metric::GoldPerHour => per_hour(self.gold as f64, time),
+            Metric::KillsPerHour => per_hour(self.enemy_killed as f64, time),
+            Metric::XpPerHour => per_hour(self.xp as f64, time),
+            Metric::Survival => if self.died { 0.0 } else { 1.0 },
+        }
+    }
+}
+
+
+impl SlayerTier {
+    // Every task needs at least one kill, otherwise it completes without time passing
+    pub fn validate(&self) -> Result<(), String> {
+        for task in &self.tasks {
+            if task.min_kills == 0 || task.min_kills > task.max_kills {
+                return Err(format!("Invalid kill range {}-{}, expected 1 <= min <= max", task.min_kills, task.max_kills));
+            }
+        }
+        WeightedIndex::new(self.tasks.iter().map(|t| t.weight)).map(|_| ()).map_err(|e| format!("Invalid slayer tier: {}", e))
+    }
+}
+
+
+pub fn sim(config: &FightingSimConfig, tier: &SlayerTier) -> Result<SlayerSimResult, String> {
+    tier.validate()?;
+    let mut rng = rand::thread_rng();
+    let weights = WeightedIndex::new(tier.tasks.iter().map(|t| t.weight)).map_err(|e| e.to_string())?;
+
+    let mut state = FightState::new(config);
+    let mut result = SlayerSimResult::default();
+    let mut task_time = dec!(0);
+
+    let max_time = config.max_time();
+    while state.can_fight(config) && state.time < max_time {
+        // Draw a new task
+        let task = &tier.tasks[weights.sample(&mut rng)];
+        let kills = rng.gen_range(task.min_kills..=task.max_kills);
+        let task_start_time = state.time;
+        let mut task_killed = 0;
+
+        while task_killed < kills && state.can_fight(config) && state.time < max_time {
+            if fight(config, &task.monster, &mut state, max_time, &mut rng) {
+                state.time += RESPAWN_TIME;
+                task_killed += 1;
+                result.coins += task.coins_per_kill;
+                result.xp += task.xp_per_kill;
+            }
+        }
+        result.enemy_killed += task_killed;
+        if task_killed == kills {
+            result.tasks_completed += 1;
+            task_time += state.time - task_start_time;
+        }
+    }
+
+    result.time = state.time.to_u32().unwrap();
+    result.task_time = task_time.to_u32().unwrap();
+    result.died = !state.is_alive();
+    result.gold = state.gold;
+    result.consumed = state.consumed(config);
+    result.loot = state.loot;
+    Ok(result)
+}
+
+
+pub fn format_slayer_results(results: &[SlayerSimResult]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    let mut time: Vec<f64> = Vec::new();
+    let mut tasks: Vec<f64> = Vec::new();
+    let mut coins: Vec<f64> = Vec::new();
+    let mut xp: Vec<f64> = Vec::new();
+    let mut task_time_sum = 0.0;
+    let mut deaths = 0.0;
+
+    for r in results {
+        time.push(r.time as f64);
+        tasks.push(r.tasks_completed as f64);
+        coins.push(r.coins as f64);
+        xp.push(r.xp as f64);
+        task_time_sum += r.task_time as f64;
+        if r.died {
+            deaths += 1.0;
+        }
+    }
+    let hours = time.iter().sum::<f64>() / 3600.0;
+    let tasks_sum = tasks.iter().sum::<f64>();
+    let mean_task_time = if tasks_sum > 0.0 { task_time_sum / tasks_sum } else { 0.0 };
+
+    format!(
+        indoc!(r#"
+        Mean time: {}
+        Died: {:.2}%
+        ------------------------
+        Mean tasks completed: {:.2}
+        Tasks per hour: {:.2}
+        Mean task time: {}
+        ------------------------
+        Mean slayer coins: {:.2}
+        Slayer coins per hour: {:.2}
+        Mean slayer XP: {:.2}
+        Slayer XP per hour: {:.2}
+        "#),
+        format_duration_as_hms(mean(&time)),
+        deaths / results.len() as f64 * 100.0,
+        mean(&tasks),
+        tasks_sum / hours,
+        format_duration_as_hms(mean_task_time),
+        mean(&coins),
+        coins.iter().sum::<f64>() / hours,
+        mean(&xp),
+        xp.iter().sum::<f64>() / hours,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(min_kills: u32, max_kills: u32) -> SlayerTask {
+        SlayerTask {
+            monster: Monster::new(100, 0, 10, 0.3, 3.0),
+            weight: 1,
+            min_kills,
+            max_kills,
+            ..SlayerTask::default()
+        }
+    }
+
+    #[test]
+    fn validates_tiers() {
+        assert!(SlayerTier { tasks: Vec::new() }.validate().is_err());
+        assert!(SlayerTier { tasks: vec![task(0, 5)] }.validate().is_err());
+        assert!(SlayerTier { tasks: vec![task(5, 3)] }.validate().is_err());
+        assert!(SlayerTier { tasks: vec![SlayerTask { weight: 0, ..task(1, 3) }] }.validate().is_err());
+        assert!(SlayerTier { tasks: vec![task(1, 3)] }.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_tier_is_an_error() {
+        let config = FightingSimConfig::new(720, 8, 8, 1, 111, 0.76, 3.0, 300, 0, 116, 0.35, 2.4);
+        assert!(sim(&config, &SlayerTier { tasks: vec![task(0, 0)] }).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::ops::AddAssign;
+use std::path::Path;
+use indoc::indoc;
+use serde::{Deserialize, Serialize};
+use super::profit::{add_item, Items, Profit};
+
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FamiliarBonus {
+    pub success_chance: f32,  // added to the steal success chance or the player hit chance
+    pub damage: f32,  // 0.1 is +10% damage
+    pub damage_reduction: f32,  // 0.1 is -10% damage taken
+    pub regen: f32,  // 0.5 is +50% health regeneration
+    pub gold: f32,  // 0.1 is +10% gold
+}
+
+impl AddAssign for FamiliarBonus {
+    fn add_assign(&mut self, other: Self) {
+        self.success_chance += other.success_chance;
+        self.damage += other.damage;
+        self.damage_reduction += other.damage_reduction;
+        self.regen += other.regen;
+        self.gold += other.gold;
+    }
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Familiar {
+    pub name: String,  // of the tablet
+    pub bonus: FamiliarBonus,
+    pub tablets: u32,  // stock
+    #[serde(default = "one")]
+    pub charges_per_action: u32,  // tablets used by every steal or attack
+}
+
+fn one() -> u32 {
+    1
+}
+
+
+// Extra bonus while both familiars are summoned
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Synergy {
+    pub familiars: (String, String),
+    pub bonus: FamiliarBonus,
+}
+
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Summoning {
+    pub familiars: Vec<Familiar>,
+    pub synergies: Vec<Synergy>,
+}
+
+impl Summoning {
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let json = std::fs::read(path)?;
+        let summoning: Summoning = serde_json::from_slice(&json)?;
+        Ok(summoning)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.familiars.is_empty()
+    }
+}
+
+
+// What the familiars added to the run, worked out at the time each bonus applied
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Contribution {
+    pub successes: u32,  // steals or hits that would have failed without the familiars
+    pub gold: f64,
+    pub damage: f64,
+    pub damage_prevented: f64,
+    pub healing: f64,
+}
+
+impl Contribution {
+    // Damage taken with only the other reductions, less the damage taken with the
+    // familiars' reduction on top
+    pub fn prevent_damage(&mut self, raw_damage: f64, other_reduction: f32, familiar_reduction: f32) {
+        let taken = |reduction: f32| raw_damage * (1.0 - reduction as f64).max(0.0);
+        self.damage_prevented += taken(other_reduction) - taken(other_reduction + familiar_reduction);
+    }
+}
+
+
+#[derive(Debug, Default, Clone)]
+pub struct SummoningState {
+    pub tablets: Vec<u32>,
+    pub used: Vec<u32>,
+    pub actions: u32,
+    pub synergy_actions: u32,
+    pub contribution: Contribution,
+}
+
+impl SummoningState {
+    pub fn new(summoning: &Summoning) -> Self {
+        Self {
+            tablets: summoning.familiars.iter().map(|f| f.tablets).collect(),
+            used: vec![0; summoning.familiars.len()],
+            ..Self::default()
+        }
+    }
+
+    // Familiars with enough tablets left for the next action
+    fn summoned<'a>(&'a self, summoning: &'a Summoning) -> impl Iterator<Item = (usize, &'a Familiar)> {
+        summoning.familiars.iter().enumerate().filter(|(i, f)| self.tablets[*i] >= f.charges_per_action.max(1))
+    }
+
+    fn synergies<'a>(&'a self, summoning: &'a Summoning) -> impl Iterator<Item = &'a Synergy> {
+        let names: Vec<&str> = self.summoned(summoning).map(|(_, f)| f.name.as_str()).collect();
+        summoning.synergies.iter()
+            .filter(move |s| names.contains(&s.familiars.0.as_str()) && names.contains(&s.familiars.1.as_str()))
+    }
+
+    // Bonus of the summoned familiars, without using any charges
+    pub fn bonus(&self, summoning: &Summoning) -> FamiliarBonus {
+        let mut bonus = FamiliarBonus::default();
+        for (_, familiar) in self.summoned(summoning) {
+            bonus += familiar.bonus;
+        }
+        for synergy in self.synergies(summoning) {
+            bonus += synergy.bonus;
+        }
+        bonus
+    }
+
+    // Uses the charges of every summoned familiar for one action
+    pub fn use_charges(&mut self, summoning: &Summoning) -> FamiliarBonus {
+        let summoned: Vec<(usize, u32)> = self.summoned(summoning).map(|(i, f)| (i, f.charges_per_action.max(1))).collect();
+        // Actions without any familiar out are not counted
+        if summoned.is_empty() {
+            return FamiliarBonus::default();
+        }
+        let bonus = self.bonus(summoning);
+        self.actions += 1;
+        if self.synergies(summoning).next().is_some() {
+            self.synergy_actions += 1;
+        }
+        for (i, charges) in summoned {
+            self.tablets[i] -= charges;
+            self.used[i] += charges;
+        }
+        bonus
+    }
+
+    pub fn result(&self, summoning: &Summoning) -> SummoningResult {
+        let mut tablets_used = Items::new();
+        for (familiar, used) in summoning.familiars.iter().zip(&self.used) {
+            add_item(&mut tablets_used, &familiar.name, *used);
+        }
+        SummoningResult {
+            tablets_used,
+            actions: self.actions,
+            synergy_actions: self.synergy_actions,
+            contribution: self.contribution,
+        }
+    }
+}
+
+
+#[derive(Debug, Default, Clone)]
+pub struct SummoningResult {
+    pub tablets_used: Items,
+    pub actions: u32,
+    pub synergy_actions: u32,
+    pub contribution: Contribution,
+}
+
+
+pub trait Summoned: Profit {
+    fn summoning(&self) -> &SummoningResult;
+}
+
+
+pub fn format_summoning_results<R: Summoned>(results: &[R]) -> String {
+    let hours = results.iter().map(|r| r.time()).sum::<f64>() / 3600.0;
+    if hours <= 0.0 {
+        return String::new();
+    }
+    let mut tablets_used = Items::new();
+    let mut actions = 0.0;
+    let mut synergy_actions = 0.0;
+    let mut contribution = Contribution::default();
+    for r in results {
+        let summoning = r.summoning();
+        for (tablet, used) in &summoning.tablets_used {
+            add_item(&mut tablets_used, tablet, *used);
+        }
+        actions += summoning.actions as f64;
+        synergy_actions += summoning.synergy_actions as f64;
+        contribution.successes += summoning.contribution.successes;
+        contribution.gold += summoning.contribution.gold;
+        contribution.damage += summoning.contribution.damage;
+        contribution.damage_prevented += summoning.contribution.damage_prevented;
+        contribution.healing += summoning.contribution.healing;
+    }
+
+    let mut output = String::new();
+    for (tablet, used) in &tablets_used {
+        output += &format!("{} per hour: {:.2}\n", tablet, *used as f64 / hours);
+    }
+    output + &format!(
+        indoc!(r#"
+        Synergy active: {:.2}%
+        ------------------------
+        Familiar successes per hour: {:.2}
+        Familiar gold per hour: {:.2}
+        Familiar damage per hour: {:.2}
+        Familiar damage prevented per hour: {:.2}
+        Familiar healing per hour: {:.2}
+        "#),
+        if actions > 0.0 { synergy_actions / actions * 100.0 } else { 0.0 },
+        contribution.successes as f64 / hours,
+        contribution.gold / hours,
+        contribution.damage / hours,
+        contribution.damage_prevented / hours,
+        contribution.healing / hours,
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn familiar(name: &str, tablets: u32, charges_per_action: u32, damage: f32) -> Familiar {
+        Familiar {
+            name: name.to_string(),
+            bonus: FamiliarBonus { damage, ..FamiliarBonus::default() },
+            tablets,
+            charges_per_action,
+        }
+    }
+
+    fn summoning() -> Summoning {
+        Summoning {
+            familiars: vec![familiar("Wolf", 3, 1, 0.1), familiar("Ent", 4, 2, 0.2)],
+            synergies: vec![Synergy {
+                familiars: ("Wolf".to_string(), "Ent".to_string()),
+                bonus: FamiliarBonus { gold: 0.5, ..FamiliarBonus::default() },
+            }],
+        }
+    }
+
+    #[test]
+    fn charges_are_used_until_the_tablets_run_out() {
+        let summoning = summoning();
+        let mut state = SummoningState::new(&summoning);
+        let bonuses: Vec<FamiliarBonus> = (0..4).map(|_| state.use_charges(&summoning)).collect();
+        assert!((bonuses[0].damage - 0.3).abs() < 1e-6);
+        assert_eq!(bonuses[0].gold, 0.5);
+        // The Ent runs out after two actions, the synergy goes with it
+        assert!((bonuses[2].damage - 0.1).abs() < 1e-6);
+        assert_eq!(bonuses[2].gold, 0.0);
+        assert_eq!(bonuses[3], FamiliarBonus::default());
+        assert_eq!(state.tablets, vec![0, 0]);
+        assert_eq!(state.used, vec![3, 4]);
+        assert_eq!(state.actions, 3);
+        assert_eq!(state.synergy_actions, 2);
+    }
+
+    #[test]
+    fn bonus_does_not_use_charges() {
+        let summoning = summoning();
+        let state = SummoningState::new(&summoning);
+        assert!((state.bonus(&summoning).damage - 0.3).abs() < 1e-6);
+        assert_eq!(state.tablets, vec![3, 4]);
+        assert_eq!(state.actions, 0);
+    }
+
+    #[test]
+    fn result_counts_the_tablets_used() {
+        let summoning = summoning();
+        let mut state = SummoningState::new(&summoning);
+        state.use_charges(&summoning);
+        let result = state.result(&summoning);
+        assert_eq!(result.tablets_used["Wolf"], 1);
+        assert_eq!(result.tablets_used["Ent"], 2);
+        assert_eq!(result.synergy_actions, 1);
+    }
+
+    #[test]
+    fn prevented_damage_is_what_the_familiars_took_off() {
+        let mut contribution = Contribution::default();
+        contribution.prevent_damage(100.0, 0.2, 0.1);
+        assert!((contribution.damage_prevented - 10.0).abs() < 1e-6);
+        // Reductions above 100% do not prevent more than the damage left
+        contribution.prevent_damage(100.0, 0.95, 0.1);
+        assert!((contribution.damage_prevented - 15.0).abs() < 1e-4);
+    }
+}
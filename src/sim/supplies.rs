@@ -0,0 +1,78 @@
+use rand::Rng;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+
+// Arrows, bolts, runes and other items used up by every attack
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Supply {
+    pub name: String,
+    pub per_attack: u32,
+    pub stock: u32,
+    pub preserve_chance: f32,
+    pub price: u32,
+}
+
+
+// Player attack stats, used to switch style once the supplies run out
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct AttackStyle {
+    pub damage_min: u16,
+    pub damage_max: u16,
+    pub hit_chance: f32,
+    pub attack_interval: Decimal,
+}
+
+
+#[derive(Debug, Default, Clone)]
+pub struct SupplyState {
+    pub stock: Vec<u32>,
+    pub used: Vec<u32>,
+    pub ran_out_at: Option<Decimal>,
+}
+
+impl SupplyState {
+    pub fn new(supplies: &[Supply]) -> Self {
+        Self {
+            stock: supplies.iter().map(|s| s.stock).collect(),
+            used: vec![0; supplies.len()],
+            ran_out_at: None,
+        }
+    }
+
+    // Uses the supplies for one attack. Returns false once any of them has run out.
+    pub fn consume<R: Rng>(&mut self, supplies: &[Supply], time: Decimal, rng: &mut R) -> bool {
+        if self.ran_out_at.is_some() {
+            return false;
+        }
+        if supplies.iter().zip(&self.stock).any(|(s, stock)| *stock < s.per_attack) {
+            self.ran_out_at = Some(time);
+            return false;
+        }
+        for (i, supply) in supplies.iter().enumerate() {
+            if rng.gen::<f32>() >= supply.preserve_chance {
+                self.stock[i] -= supply.per_attack;
+                self.used[i] += supply.per_attack;
+            }
+        }
+        true
+    }
+
+    pub fn cost(&self, supplies: &[Supply]) -> f64 {
+        supplies.iter().zip(&self.used).map(|(s, used)| s.price as f64 * *used as f64).sum()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_costs_do_not_overflow() {
+        let supplies = [Supply { name: "Rune".to_string(), price: 100_000, ..Supply::default() }];
+        let state = SupplyState { used: vec![1_000_000], ..SupplyState::new(&supplies) };
+        assert_eq!(state.cost(&supplies), 100_000_000_000.0);
+    }
+}
@@ -0,0 +1,234 @@
+use std::str::FromStr;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use super::batch::{run_batch, Estimate, Metric, Metrics};
+
+// Decimal fields are times in seconds and the sims only step on this grid
+pub const DECIMAL_STEP: f64 = 0.1;
+
+
+// Returns a copy of the config with one field changed. Nested fields are separated
+// by dots, e.g. `enemy.health`.
+pub fn set_field<C: Serialize + DeserializeOwned>(config: &C, field: &str, value: f64) -> Result<C, String> {
+    let mut json = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let mut target = &mut json;
+    for key in field.split('.') {
+        target = target.get_mut(key).ok_or_else(|| format!("Unknown field: {}", field))?;
+    }
+    *target = match target {
+        // Decimals are stored as strings, rounded to the grid so timers like
+        // `time % steal_interval` still line up. They are intervals, so never 0.
+        Value::String(_) => {
+            let decimal = Decimal::try_from(value).map_err(|e| format!("Invalid value {} for {}: {}", value, field, e))?;
+            Value::String(decimal.round_dp(1).max(dec!(0.1)).to_string())
+        }
+        Value::Number(n) if n.is_f64() => serde_json::json!(value),
+        Value::Number(_) => serde_json::json!(value.round() as i64),
+        _ => return Err(format!("Field {} is not a number", field)),
+    };
+    serde_json::from_value(json).map_err(|e| format!("Invalid value {} for {}: {}", value, field, e))
+}
+
+pub fn get_field<C: Serialize>(config: &C, field: &str) -> Result<f64, String> {
+    let json = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let mut target = &json;
+    for key in field.split('.') {
+        target = target.get(key).ok_or_else(|| format!("Unknown field: {}", field))?;
+    }
+    match target {
+        Value::String(s) => s.parse().map_err(|_| format!("Field {} is not a number", field)),
+        Value::Number(n) => Ok(n.as_f64().unwrap_or_default()),
+        _ => Err(format!("Field {} is not a number", field)),
+    }
+}
+
+// True if the field is a Decimal, so it moves in steps of DECIMAL_STEP
+pub fn is_decimal_field<C: Serialize>(config: &C, field: &str) -> Result<bool, String> {
+    let json = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let mut target = &json;
+    for key in field.split('.') {
+        target = target.get(key).ok_or_else(|| format!("Unknown field: {}", field))?;
+    }
+    Ok(target.is_string())
+}
+
+// Names of the numeric top level fields of the config
+pub fn numeric_fields<C: Serialize>(config: &C) -> Vec<String> {
+    match serde_json::to_value(config) {
+        Ok(Value::Object(map)) => map.iter()
+            .filter(|(_, v)| v.is_number() || v.as_str().is_some_and(|s| s.parse::<f64>().is_ok()))
+            .map(|(k, _)| k.clone())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SweepAxis {
+    pub field: String,
+    pub from: f64,
+    pub to: f64,
+    pub steps: usize,
+}
+
+impl SweepAxis {
+    pub fn values(&self) -> Vec<f64> {
+        if self.steps <= 1 {
+            return vec![self.from];
+        }
+        let step = (self.to - self.from) / (self.steps - 1) as f64;
+        (0..self.steps).map(|i| self.from + step * i as f64).collect()
+    }
+}
+
+// Parses `field=from:to:steps`
+impl FromStr for SweepAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid sweep {}, expected field=from:to:steps", s);
+        let (field, range) = s.split_once('=').ok_or_else(error)?;
+        let parts: Vec<&str> = range.split(':').collect();
+        if parts.len() != 3 {
+            return Err(error());
+        }
+        Ok(Self {
+            field: field.to_string(),
+            from: parts[0].parse().map_err(|_| error())?,
+            to: parts[1].parse().map_err(|_| error())?,
+            steps: parts[2].parse().map_err(|_| error())?,
+        })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SweepPoint {
+    pub values: Vec<f64>,  // one per axis
+    pub estimate: Estimate,
+}
+
+
+// Runs a batch at every point of the grid made by one or two axes
+pub fn sweep<C, R, F>(config: &C, axes: &[SweepAxis], metric: Metric, iterations: usize, sim: F) -> Result<Vec<SweepPoint>, String>
+where
+    C: Serialize + DeserializeOwned,
+    R: Metrics,
+    F: Fn(&C) -> R,
+{
+    if axes.is_empty() {
+        return Err("Nothing to sweep".to_string());
+    }
+    let mut grid: Vec<Vec<f64>> = vec![Vec::new()];
+    for axis in axes {
+        grid = grid.iter()
+            .flat_map(|point| axis.values().into_iter().map(move |v| [point.clone(), vec![v]].concat()))
+            .collect();
+    }
+
+    let mut points = Vec::new();
+    for values in grid {
+        let mut point_config = set_field(config, &axes[0].field, values[0])?;
+        for (axis, value) in axes.iter().zip(&values).skip(1) {
+            point_config = set_field(&point_config, &axis.field, *value)?;
+        }
+        let runs = run_batch(iterations, || sim(&point_config));
+        points.push(SweepPoint {
+            values,
+            estimate: Estimate::from_results(&runs, metric),
+        });
+    }
+    Ok(points)
+}
+
+
+pub fn format_sweep_csv(axes: &[SweepAxis], metric: Metric, points: &[SweepPoint]) -> String {
+    let mut header: Vec<String> = axes.iter().map(|a| a.field.clone()).collect();
+    header.push(metric.to_string());
+    header.push("std_error".to_string());
+    let mut output = header.join(",") + "\n";
+    for point in points {
+        let mut row: Vec<String> = point.values.iter().map(|v| v.to_string()).collect();
+        row.push(format!("{:.4}", point.estimate.mean));
+        row.push(format!("{:.4}", point.estimate.std_error));
+        output += &(row.join(",") + "\n");
+    }
+    output
+}
+
+
+// One row per point for a single axis, a grid of means for two axes
+pub fn format_sweep_table(axes: &[SweepAxis], metric: Metric, points: &[SweepPoint]) -> String {
+    if axes.len() == 1 {
+        let mut output = format!("{:>14} | {}\n", axes[0].field, metric);
+        for point in points {
+            output += &format!("{:>14.3} | {}\n", point.values[0], point.estimate);
+        }
+        return output;
+    }
+    let columns = axes[1].values();
+    let mut output = format!("{:>14} \\ {}\n{:>14}", axes[0].field, axes[1].field, "");
+    for column in &columns {
+        output += &format!(" | {:>12.3}", column);
+    }
+    output += "\n";
+    for row in points.chunks(columns.len()) {
+        output += &format!("{:>14.3}", row[0].values[0]);
+        for point in row {
+            output += &format!(" | {:>12.2}", point.estimate.mean);
+        }
+        output += "\n";
+    }
+    output
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::thieving::ThievingSimConfig;
+    use super::*;
+
+    fn config() -> ThievingSimConfig {
+        ThievingSimConfig::new(dec!(1), 1, 100, dec!(2.6), 0.5, 1, 10, 10, 20)
+    }
+
+    #[test]
+    fn decimal_fields_are_snapped_to_the_grid() {
+        let config = set_field(&config(), "steal_interval", 2.667).unwrap();
+        assert_eq!(config.steal_interval, dec!(2.7));
+        assert_eq!(get_field(&config, "steal_interval").unwrap(), 2.7);
+        let config = set_field(&config, "health_regeneration_interval", 0.04).unwrap();
+        assert_eq!(config.health_regeneration_interval, dec!(0.1));
+        let config = set_field(&config, "steal_interval", 0.0).unwrap();
+        assert_eq!(config.steal_interval, dec!(0.1));
+    }
+
+    #[test]
+    fn integer_fields_are_rounded() {
+        let config = set_field(&config(), "max_health", 149.6).unwrap();
+        assert_eq!(config.max_health, 150);
+        assert_eq!(get_field(&config, "max_health").unwrap(), 150.0);
+    }
+
+    #[test]
+    fn unknown_and_non_numeric_fields_are_errors() {
+        assert!(set_field(&config(), "missing", 1.0).is_err());
+        assert!(set_field(&config(), "drops", 1.0).is_err());
+        assert!(get_field(&config(), "missing").is_err());
+        assert!(is_decimal_field(&config(), "steal_interval").unwrap());
+        assert!(!is_decimal_field(&config(), "max_health").unwrap());
+    }
+
+    #[test]
+    fn sweeping_intervals_from_zero_runs() {
+        let mut config = config();
+        config.max_time = Some(dec!(60));
+        let axis: SweepAxis = "health_regeneration_interval=0:8:3".parse().unwrap();
+        let points = sweep(&config, &[axis], Metric::GoldPerHour, 2, crate::thieving::sim).unwrap();
+        assert_eq!(points.len(), 3);
+    }
+}
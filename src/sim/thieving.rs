@@ -6,18 +6,86 @@ use rust_decimal::Decimal;
 use rust_decimal::prelude::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use super::format_duration_as_hms;
+use super::batch::{per_hour, Metric, Metrics};
+use super::food::{Food, FoodState};
+use super::potion::{Potion, PotionState};
+use super::profit::{add_item, ItemDrop, Items, Profit};
+use super::session::{Chained, EndState};
+use super::summoning::{Summoned, Summoning, SummoningResult, SummoningState};
+use super::trace::{EventKind, Trace};
+
+pub const MAX_TIME: Decimal = dec!(28800);  // 8 hours
+pub const XP_PER_STEAL: i32 = 410;
 
 // Определение структур, аналогичных NamedTuple в Python
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct ThievingSimResult {
     time: i32,
     money_earned: i32,
     success_thieving_count: i32,
     failed_thieving_count: i32,
     thieving_count: i32,
+    potions_used: i32,
+    xp: i32,
+    died: bool,
+    loot: Items,
+    consumed: Items,
+    end: EndState,
+    summoning: SummoningResult,
 }
 
-#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+impl Profit for ThievingSimResult {
+    fn time(&self) -> f64 {
+        self.time as f64
+    }
+
+    fn gold(&self) -> f64 {
+        self.money_earned as f64
+    }
+
+    fn loot(&self) -> &Items {
+        &self.loot
+    }
+
+    fn consumed(&self) -> &Items {
+        &self.consumed
+    }
+}
+
+impl Chained for ThievingSimResult {
+    fn end_state(&self) -> EndState {
+        self.end
+    }
+
+    fn xp(&self) -> f64 {
+        self.xp as f64
+    }
+
+    fn died(&self) -> bool {
+        self.died
+    }
+}
+
+impl Summoned for ThievingSimResult {
+    fn summoning(&self) -> &SummoningResult {
+        &self.summoning
+    }
+}
+
+impl Metrics for ThievingSimResult {
+    fn metric(&self, metric: Metric) -> f64 {
+        let time = self.time as f64;
+        match metric {
+            Metric::Time => time,
+            Metric::GoldPerHour => per_hour(self.money_earned as f64, time),
+            Metric::KillsPerHour => per_hour(self.success_thieving_count as f64, time),
+            Metric::XpPerHour => per_hour(self.xp as f64, time),
+            Metric::Survival => if self.died { 0.0 } else { 1.0 },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ThievingSimConfig {
     pub health_regeneration_interval: Decimal, // in seconds
     pub health_regeneration_amount: i32,
@@ -28,6 +96,24 @@ pub struct ThievingSimConfig {
     pub max_damage: i32,
     pub min_gold: i32,
     pub max_gold: i32,
+    #[serde(default)]
+    pub potion: Option<Potion>,
+    #[serde(default)]
+    pub food: Option<Food>,
+    #[serde(default)]
+    pub drops: Vec<ItemDrop>,  // rolled on every successful steal
+    #[serde(default = "default_xp_per_steal")]
+    pub xp_per_steal: i32,
+    #[serde(default)]
+    pub start_health: Option<i32>,  // max health if not set
+    #[serde(default)]
+    pub max_time: Option<Decimal>,  // MAX_TIME if not set
+    #[serde(default)]
+    pub summoning: Summoning,
+}
+
+fn default_xp_per_steal() -> i32 {
+    XP_PER_STEAL
 }
 
 impl ThievingSimConfig {
@@ -43,46 +129,111 @@ impl ThievingSimConfig {
             max_damage,
             min_gold,
             max_gold,
+            potion: None,
+            food: None,
+            drops: Vec::new(),
+            xp_per_steal: XP_PER_STEAL,
+            start_health: None,
+            max_time: None,
+            summoning: Summoning::default(),
         }
     }
+
+    pub fn max_time(&self) -> Decimal {
+        self.max_time.unwrap_or(MAX_TIME)
+    }
 }
 
 pub fn sim(config: &ThievingSimConfig) -> ThievingSimResult {
-    let mut rng = rand::thread_rng();
-    let mut current_health = config.max_health;
+    sim_with_rng(config, &mut rand::thread_rng())
+}
+
+// Same as `sim`, with the random source passed in so runs can be seeded
+pub fn sim_with_rng<R: Rng>(config: &ThievingSimConfig, rng: &mut R) -> ThievingSimResult {
+    simulate(config, rng, &mut Trace::default())
+}
+
+// Same as `sim_with_rng`, also returning every event of the run
+pub fn sim_traced<R: Rng>(config: &ThievingSimConfig, rng: &mut R) -> (ThievingSimResult, Trace) {
+    let mut trace = Trace::recording();
+    let result = simulate(config, rng, &mut trace);
+    (result, trace)
+}
+
+fn simulate<R: Rng>(config: &ThievingSimConfig, rng: &mut R, trace: &mut Trace) -> ThievingSimResult {
+    let mut current_health = config.start_health.map_or(config.max_health, |h| cmp::min(h, config.max_health));
     let mut gold_earn = 0;
     let mut success_thieving_count = 0;
     let mut failed_thieving_count = 0;
     let mut thieving_count = 0;
+    let mut potion = PotionState::new(config.potion.as_ref());
+    let mut food = FoodState::new(config.food.as_ref());
+    let mut summoning = SummoningState::new(&config.summoning);
+    let mut loot = Items::new();
     let mut time = dec!(0.0);
-    let max_time = Decimal::from(60 * 60 * 8);
 
+    let max_time = config.max_time();
     while current_health > 0 && time < max_time {
         // Attempt to steal every 3 seconds
         if time % config.steal_interval == dec!(0) {
             thieving_count += 1;
-            if rng.gen::<f32>() > config.steal_success_chance {
+            let bonus = potion.use_charge(config.potion.as_ref());
+            let familiar = summoning.use_charges(&config.summoning);
+            let success_chance = config.steal_success_chance + bonus.success_chance;
+            let roll = rng.gen::<f32>();
+            if roll > success_chance + familiar.success_chance {
                 // Failed steal attempt, take damage and get stunned
                 failed_thieving_count += 1;
-                let damage = rng.gen_range(config.min_damage..=config.max_damage);
+                let raw_damage = rng.gen_range(config.min_damage..=config.max_damage);
+                let damage = (raw_damage as f32 * (1.0 - bonus.damage_reduction - familiar.damage_reduction).max(0.0)) as i32;
+                summoning.contribution.prevent_damage(raw_damage as f64, bonus.damage_reduction, familiar.damage_reduction);
                 current_health -= damage;
+                trace.record(time, current_health, EventKind::Steal { success: false, gold: 0 });
+                trace.record(time, current_health, EventKind::DamageTaken { damage: damage as u32 });
 
                 // Check if health drops below zero
                 if current_health <= 0 {
+                    trace.record(time, current_health, EventKind::Death);
                     break;
                 }
+                let healed = food.eat(config.food.as_ref(), current_health as u32, config.max_health as u32);
+                current_health += healed as i32;
+                if healed > 0 {
+                    trace.record(time, current_health, EventKind::Eat { heal: healed });
+                }
 
                 // Stunned for 3 seconds
+                trace.record(time, current_health, EventKind::Stun { seconds: 3.0 });
                 time += dec!(3.0);
             } else {
                 success_thieving_count += 1;
-                gold_earn += rng.gen_range(config.min_gold..=config.max_gold);
+                if roll > success_chance {
+                    summoning.contribution.successes += 1;
+                }
+                let gold = rng.gen_range(config.min_gold..=config.max_gold);
+                let extra_gold = (gold as f32 * familiar.gold) as i32;
+                summoning.contribution.gold += extra_gold as f64;
+                let gold = gold + extra_gold;
+                gold_earn += gold;
+                let dropped = ItemDrop::roll(&config.drops, &mut loot, rng);
+                trace.record(time, current_health, EventKind::Steal { success: true, gold });
+                for (item, amount) in dropped {
+                    trace.record(time, current_health, EventKind::Loot { item, amount });
+                }
             }
         }
 
         // Health regeneration every 8 seconds
         if time % config.health_regeneration_interval == dec!(0) {
-            current_health = cmp::min(current_health + config.health_regeneration_amount, config.max_health);
+            let regen_bonus = potion.bonus(config.potion.as_ref()).regen;
+            let familiar_regen = summoning.bonus(&config.summoning).regen;
+            let regen = (config.health_regeneration_amount as f32 * (1.0 + regen_bonus + familiar_regen)) as i32;
+            let regenerated = cmp::min(current_health + regen, config.max_health) - current_health;
+            summoning.contribution.healing += (config.health_regeneration_amount as f32 * familiar_regen).min(regenerated as f32) as f64;
+            current_health += regenerated;
+            if regenerated > 0 {
+                trace.record(time, current_health, EventKind::Regen { amount: regenerated as u32 });
+            }
         }
 
         // Increment time
@@ -95,16 +246,43 @@ pub fn sim(config: &ThievingSimConfig) -> ThievingSimResult {
         success_thieving_count,
         failed_thieving_count,
         thieving_count,
+        potions_used: potion.used as i32,
+        xp: success_thieving_count * config.xp_per_steal,
+        died: current_health <= 0,
+        loot,
+        consumed: consumed_items(config, &potion, &food, &summoning),
+        end: EndState {
+            health: current_health.max(0),
+            food_stock: config.food.as_ref().map(|_| food.stock),
+            potion_stock: config.potion.as_ref().map(|_| potion.stock),
+        },
+        summoning: summoning.result(&config.summoning),
+    }
+}
+
+
+fn consumed_items(config: &ThievingSimConfig, potion: &PotionState, food: &FoodState, summoning: &SummoningState) -> Items {
+    let mut consumed = Items::new();
+    if let Some(p) = &config.potion {
+        add_item(&mut consumed, &p.name, potion.used);
+    }
+    if let Some(f) = &config.food {
+        add_item(&mut consumed, &f.name, food.eaten);
+    }
+    for (tablet, used) in summoning.result(&config.summoning).tablets_used {
+        add_item(&mut consumed, &tablet, used);
     }
+    consumed
 }
 
 
 pub fn format_thieve_results(results: &[ThievingSimResult]) -> String {
     let mut mean_time = 0.0;
     let mut mean_money_earned = 0.0;
-    let mut success_thieving_count_sum = 0;
-    let mut failed_thieving_count_sum = 0;
-    let mut thieving_count_sum = 0;
+    let mut success_thieving_count_sum: i64 = 0;
+    let mut failed_thieving_count_sum: i64 = 0;
+    let mut thieving_count_sum: i64 = 0;
+    let mut potions_used_sum: i64 = 0;
 
     let mut sorted_seconds = Vec::new();
     let mut sorted_money_earned = Vec::new();
@@ -116,9 +294,10 @@ pub fn format_thieve_results(results: &[ThievingSimResult]) -> String {
     for result in results {
         mean_time += result.time as f64;
         mean_money_earned += result.money_earned as f64;
-        success_thieving_count_sum += result.success_thieving_count;
-        failed_thieving_count_sum += result.failed_thieving_count;
-        thieving_count_sum += result.thieving_count;
+        success_thieving_count_sum += result.success_thieving_count as i64;
+        failed_thieving_count_sum += result.failed_thieving_count as i64;
+        thieving_count_sum += result.thieving_count as i64;
+        potions_used_sum += result.potions_used as i64;
 
         sorted_seconds.push(result.time);
         sorted_money_earned.push(result.money_earned);
@@ -132,14 +311,20 @@ pub fn format_thieve_results(results: &[ThievingSimResult]) -> String {
         thieving_count = thieving_count_sum as f64 / results.len() as f64;
     }
 
+    let hours = sorted_seconds.iter().map(|&s| s as f64).sum::<f64>() / 3600.0;
+    let potions_per_hour = if hours > 0.0 { potions_used_sum as f64 / hours } else { 0.0 };
+
     sorted_seconds.sort();
     sorted_money_earned.sort();
 
-    let min_mean_time = sorted_seconds.iter().take(500).sum::<i32>() as f64 / 500.0;
-    let min_money_earned = sorted_money_earned.iter().take(500).sum::<i32>() as f64 / 500.0;
+    // Means of the 500 lowest and highest runs, or of every run in smaller batches
+    let tail = results.len().clamp(1, 500);
+    let tail_mean = |values: &mut dyn Iterator<Item = &i32>| values.take(tail).map(|&v| v as f64).sum::<f64>() / tail as f64;
+    let min_mean_time = tail_mean(&mut sorted_seconds.iter());
+    let min_money_earned = tail_mean(&mut sorted_money_earned.iter());
 
-    let max_mean_time = sorted_seconds.iter().rev().take(500).sum::<i32>() as f64 / 500.0;
-    let max_money_earned = sorted_money_earned.iter().rev().take(500).sum::<i32>() as f64 / 500.0;
+    let max_mean_time = tail_mean(&mut sorted_seconds.iter().rev());
+    let max_money_earned = tail_mean(&mut sorted_money_earned.iter().rev());
 
     format!(
         indoc!(r#"
@@ -154,6 +339,7 @@ pub fn format_thieve_results(results: &[ThievingSimResult]) -> String {
         Success thieving: {:.2}
         Failed thieving: {:.2}
         Thieving count: {:.2}
+        Potions per hour: {:.2}
         "#),
         format_duration_as_hms(mean_time),
         format_duration_as_hms(max_mean_time),
@@ -164,5 +350,45 @@ pub fn format_thieve_results(results: &[ThievingSimResult]) -> String {
         success_thieving,
         failed_thieving,
         thieving_count,
+        potions_per_hour,
     )
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(time: i32, money_earned: i32) -> ThievingSimResult {
+        ThievingSimResult { time, money_earned, ..ThievingSimResult::default() }
+    }
+
+    #[test]
+    fn tail_means_use_every_run_in_small_batches() {
+        let output = format_thieve_results(&[result(100, 10), result(200, 30)]);
+        assert!(output.contains("Max money earned: 20.00"));
+        assert!(output.contains("Min money earned: 20.00"));
+    }
+
+    #[test]
+    fn large_batches_do_not_overflow() {
+        let results = vec![result(28800, 2_000_000); 100_000];
+        let output = format_thieve_results(&results);
+        assert!(output.contains("Max money earned: 2000000.00"));
+    }
+
+    #[test]
+    fn start_health_is_capped_at_max_health() {
+        let mut config = ThievingSimConfig::new(dec!(1), 1, 100, dec!(2.6), 1.0, 1, 10, 10, 20);
+        config.start_health = Some(10_000);
+        config.max_time = Some(dec!(1));
+        assert_eq!(sim(&config).end.health, 100);
+    }
+
+    #[test]
+    fn configs_without_xp_per_steal_use_the_default() {
+        let mut json = serde_json::to_value(ThievingSimConfig::new(dec!(1), 1, 100, dec!(2.6), 1.0, 1, 10, 10, 20)).unwrap();
+        json.as_object_mut().unwrap().remove("xp_per_steal");
+        let config: ThievingSimConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(config.xp_per_steal, XP_PER_STEAL);
+    }
+}
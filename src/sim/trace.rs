@@ -0,0 +1,208 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    Steal { success: bool, gold: i32 },
+    Hit { damage: u32, special: bool },
+    Miss,
+    DamageTaken { damage: u32 },
+    Regen { amount: u32 },
+    Stun { seconds: f64 },
+    Eat { heal: u32 },
+    Kill { gold: u32 },
+    Loot { item: String, amount: u32 },
+    Death,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub time: f64,
+    pub health: i32,  // after the event
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+
+// Events of a single run. The default trace does not record anything, so sims
+// that are not traced only pay for a branch.
+#[derive(Debug, Default, Clone)]
+pub struct Trace {
+    recording: bool,
+    pub events: Vec<Event>,
+}
+
+impl Trace {
+    pub fn recording() -> Self {
+        Self {
+            recording: true,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, time: Decimal, health: i32, kind: EventKind) {
+        if self.recording {
+            self.events.push(Event {
+                time: time.to_f64().unwrap_or_default(),
+                health,
+                kind,
+            });
+        }
+    }
+
+    // One JSON object per line
+    pub fn to_ndjson(&self) -> String {
+        self.events.iter()
+            .map(|event| serde_json::to_string(event).unwrap() + "\n")
+            .collect()
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        std::fs::write(path, self.to_ndjson())
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let text = std::fs::read_to_string(path)?;
+        let events = text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Event>, _>>()?;
+        Ok(Self { recording: true, events })
+    }
+}
+
+
+pub fn format_trace(trace: &Trace) -> String {
+    let mut output = String::new();
+    for event in &trace.events {
+        let description = match &event.kind {
+            EventKind::Steal { success: true, gold } => format!("steal, {} gold", gold),
+            EventKind::Steal { success: false, .. } => "failed steal".to_string(),
+            EventKind::Hit { damage, special: true } => format!("special hit for {}", damage),
+            EventKind::Hit { damage, .. } => format!("hit for {}", damage),
+            EventKind::Miss => "miss".to_string(),
+            EventKind::DamageTaken { damage } => format!("took {} damage", damage),
+            EventKind::Regen { amount } => format!("regenerated {}", amount),
+            EventKind::Stun { seconds } => format!("stunned for {}s", seconds),
+            EventKind::Eat { heal } => format!("ate, healed {}", heal),
+            EventKind::Kill { gold } => format!("enemy killed, {} gold", gold),
+            EventKind::Loot { item, amount } => format!("looted {} x{}", item, amount),
+            EventKind::Death => "died".to_string(),
+        };
+        output += &format!(
+            "{:>8.1}s | hp {:>5} | {}\n",
+            event.time,
+            event.health,
+            description,
+        );
+    }
+    output
+}
+
+
+// Which run of a batch to look at, ranked by a metric
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RunPick {
+    Worst,
+    Median,
+    Best,
+}
+
+impl RunPick {
+    pub const ALL: [RunPick; 3] = [RunPick::Worst, RunPick::Median, RunPick::Best];
+}
+
+impl fmt::Display for RunPick {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RunPick::Worst => "worst",
+            RunPick::Median => "median",
+            RunPick::Best => "best",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for RunPick {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RunPick::ALL.iter()
+            .find(|p| p.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown run: {}, expected one of worst, median, best", s))
+    }
+}
+
+
+// Runs a seeded batch and returns the seed of the picked run, so it can be
+// replayed with tracing on. Runs are ranked by the value `score` returns.
+pub fn pick_seed<F: Fn(&mut StdRng) -> f64>(iterations: usize, pick: RunPick, score: F) -> u64 {
+    let base = rand::random::<u32>() as u64;
+    let mut runs: Vec<(f64, u64)> = (0..iterations.max(1) as u64)
+        .map(|i| (score(&mut StdRng::seed_from_u64(base + i)), base + i))
+        .collect();
+    runs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    match pick {
+        RunPick::Worst => runs[0].1,
+        RunPick::Median => runs[runs.len() / 2].1,
+        RunPick::Best => runs[runs.len() - 1].1,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal_macros::dec;
+    use super::*;
+
+    fn trace() -> Trace {
+        let mut trace = Trace::recording();
+        trace.record(dec!(0.5), 100, EventKind::Steal { success: true, gold: 42 });
+        trace.record(dec!(1.5), 90, EventKind::DamageTaken { damage: 10 });
+        trace.record(dec!(2), 90, EventKind::Stun { seconds: 1.5 });
+        trace.record(dec!(3), 90, EventKind::Loot { item: "Gem".to_string(), amount: 2 });
+        trace.record(dec!(4), 0, EventKind::Death);
+        trace
+    }
+
+    #[test]
+    fn untraced_runs_record_nothing() {
+        let mut trace = Trace::default();
+        trace.record(dec!(1), 100, EventKind::Miss);
+        assert!(trace.events.is_empty());
+    }
+
+    #[test]
+    fn events_are_one_flat_json_object_per_line() {
+        let ndjson = trace().to_ndjson();
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], r#"{"time":0.5,"health":100,"event":"steal","success":true,"gold":42}"#);
+        assert_eq!(lines[4], r#"{"time":4.0,"health":0,"event":"death"}"#);
+    }
+
+    #[test]
+    fn ndjson_round_trips_through_a_file() {
+        let path = std::env::temp_dir().join(format!("trace-{}.ndjson", std::process::id()));
+        trace().save_to_file(&path).unwrap();
+        let loaded = Trace::load_from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().events, trace().events);
+    }
+
+    #[test]
+    fn parses_run_picks() {
+        assert_eq!("median".parse(), Ok(RunPick::Median));
+        assert!("mean".parse::<RunPick>().is_err());
+    }
+}